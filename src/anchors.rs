@@ -0,0 +1,269 @@
+use mdbook::utils::{new_cmark_parser, unique_id_from_content};
+use pulldown_cmark::{Event, Tag, TagEnd};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+
+/// 译文标题如何保留原文的锚点，对应 `stable-anchors = "attribute" | "html"`
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnchorStyle {
+    /// 在标题末尾加上 `{#original-anchor}`
+    Attribute,
+    /// 在标题开头插入 `<a id="original-anchor"></a>`
+    Html,
+}
+
+struct Heading {
+    range: Range<usize>,
+    /// 标题中显式写出的 `{#id}`
+    explicit_id: Option<String>,
+    text: String,
+}
+
+fn headings(content: &str) -> Vec<Heading> {
+    let mut headings = Vec::new();
+    let mut current: Option<Heading> = None;
+
+    for (event, range) in new_cmark_parser(content, false).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Heading { id, .. }) => {
+                current = Some(Heading {
+                    range,
+                    explicit_id: id.map(|id| id.to_string()),
+                    text: String::new(),
+                });
+            }
+            Event::End(TagEnd::Heading(_)) => headings.extend(current.take()),
+            Event::Text(text) | Event::Code(text) => {
+                if let Some(heading) = current.as_mut() {
+                    heading.text.push_str(&text);
+                }
+            }
+            _ => {}
+        }
+    }
+    headings
+}
+
+/// 按 mdBook 的规则计算每个标题最终的 id
+fn heading_ids(headings: &[Heading]) -> Vec<String> {
+    let mut id_counter = HashMap::new();
+    headings
+        .iter()
+        .map(|h| match &h.explicit_id {
+            Some(id) => id.clone(),
+            None => unique_id_from_content(&h.text, &mut id_counter),
+        })
+        .collect()
+}
+
+/// 让译文中的标题沿用原文的锚点。
+///
+/// 返回修改后的译文，以及「译文标题自动生成的 id → 原文 id」的对照表，用于修正链接中的 `#fragment`。
+/// 原文和译文的标题数量不一致时无法一一对应，原样返回译文。
+pub fn pin_heading_ids(source: &str, translated: &str, style: AnchorStyle) -> (String, HashMap<String, String>) {
+    let source_headings = headings(source);
+    let translated_headings = headings(translated);
+    let mut renamed = HashMap::new();
+
+    if source_headings.len() != translated_headings.len() {
+        eprintln!(
+            "\x1b[33;1mWarning:\x1b[0m found {} headings in the source but {} in the translation, heading anchors are not pinned",
+            source_headings.len(),
+            translated_headings.len()
+        );
+        return (translated.to_string(), renamed);
+    }
+
+    let source_ids = heading_ids(&source_headings);
+    let translated_ids = heading_ids(&translated_headings);
+
+    let mut output = String::with_capacity(translated.len());
+    let mut last = 0;
+    for ((heading, translated_id), source_id) in translated_headings.iter().zip(&translated_ids).zip(&source_ids) {
+        if heading.explicit_id.is_some() || translated_id == source_id {
+            continue;
+        }
+        renamed.insert(translated_id.clone(), source_id.clone());
+
+        // 标题文字所在的行：ATX 标题只有一行，Setext 标题是下划线之前的第一行
+        let line = &translated[heading.range.clone()];
+        let line = &line[..line.find('\n').unwrap_or(line.len())];
+        let line_start = heading.range.start;
+        match style {
+            AnchorStyle::Attribute => {
+                let mut text = line.trim_end();
+                // ATX 标题结尾可选的 `#` 序列会挡住属性，一并去掉
+                let without_closing = text.trim_end_matches('#');
+                if text.trim_start().starts_with('#')
+                    && without_closing.len() < text.len()
+                    && without_closing.ends_with([' ', '\t'])
+                {
+                    text = without_closing.trim_end();
+                }
+                output.push_str(&translated[last..line_start + text.len()]);
+                output.push_str(&format!(" {{#{source_id}}}"));
+                last = line_start + line.len();
+            }
+            AnchorStyle::Html => {
+                let marker = line.len() - line.trim_start_matches([' ', '#']).len();
+                output.push_str(&translated[last..line_start + marker]);
+                output.push_str(&format!("<a id=\"{source_id}\"></a>"));
+                last = line_start + marker;
+            }
+        }
+    }
+    output.push_str(&translated[last..]);
+
+    (output, renamed)
+}
+
+/// 把链接里指向译文自动生成锚点的 `#fragment` 改回原文的锚点。
+///
+/// `anchors` 以章节路径为键，值为 [`pin_heading_ids`] 返回的对照表。
+pub fn rewrite_fragment_links(
+    content: &str,
+    chapter_path: &Path,
+    anchors: &HashMap<PathBuf, HashMap<String, String>>,
+) -> String {
+    let mut edits: Vec<(Range<usize>, String)> = Vec::new();
+
+    for (event, range) in new_cmark_parser(content, false).into_offset_iter() {
+        let Event::Start(Tag::Link { dest_url, .. }) = event else {
+            continue;
+        };
+        let Some((path, fragment)) = dest_url.split_once('#') else {
+            continue;
+        };
+        if path.contains("://") || path.starts_with("mailto:") {
+            continue;
+        }
+        let target = if path.is_empty() {
+            chapter_path.to_path_buf()
+        } else {
+            resolve(chapter_path, path)
+        };
+        let Some(original) = anchors.get(&target).and_then(|ids| ids.get(fragment)) else {
+            continue;
+        };
+        let needle = format!("#{fragment}");
+        if let Some(pos) = content[range.clone()].rfind(&needle) {
+            let start = range.start + pos + 1;
+            edits.push((start..start + fragment.len(), original.clone()));
+        }
+    }
+
+    let mut output = String::with_capacity(content.len());
+    let mut last = 0;
+    for (range, replacement) in edits {
+        output.push_str(&content[last..range.start]);
+        output.push_str(&replacement);
+        last = range.end;
+    }
+    output.push_str(&content[last..]);
+    output
+}
+
+/// 把链接中的相对路径解析成相对于书籍源目录的章节路径，`.html` 视为对应的 `.md`
+fn resolve(chapter_path: &Path, link: &str) -> PathBuf {
+    let joined = chapter_path.parent().unwrap_or(Path::new("")).join(link);
+    let mut resolved = PathBuf::new();
+    for component in joined.components() {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::CurDir => {}
+            other => resolved.push(other),
+        }
+    }
+    if resolved.extension().is_some_and(|ext| ext == "html") {
+        resolved.set_extension("md");
+    }
+    resolved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 固定锚点后，译文标题的 id 与原文一致
+    fn assert_pinned(source: &str, translated: &str, style: AnchorStyle, expected: &str) -> HashMap<String, String> {
+        let (output, renamed) = pin_heading_ids(source, translated, style);
+        assert_eq!(output, expected);
+        if let AnchorStyle::Attribute = style {
+            assert_eq!(heading_ids(&headings(&output)), heading_ids(&headings(source)));
+        }
+        renamed
+    }
+
+    #[test]
+    fn duplicate_headings_keep_their_suffix() {
+        let renamed = assert_pinned(
+            "# Setup\n\nText.\n\n## Setup\n",
+            "# 安装\n\n文字。\n\n## 安装\n",
+            AnchorStyle::Attribute,
+            "# 安装 {#setup}\n\n文字。\n\n## 安装 {#setup-1}\n",
+        );
+        assert_eq!(renamed["安装"], "setup");
+        assert_eq!(renamed["安装-1"], "setup-1");
+    }
+
+    #[test]
+    fn closing_hashes_are_removed_before_the_attribute() {
+        assert_pinned(
+            "## Setup ##\n\n## C# #\n",
+            "## 安装 ##\n\n## C# #\n",
+            AnchorStyle::Attribute,
+            "## 安装 {#setup}\n\n## C# #\n",
+        );
+    }
+
+    #[test]
+    fn setext_headings() {
+        assert_pinned(
+            "Getting Started\n===============\n\nUsage\n-----\n",
+            "入门\n====\n\n用法\n----\n",
+            AnchorStyle::Attribute,
+            "入门 {#getting-started}\n====\n\n用法 {#usage}\n----\n",
+        );
+        assert_pinned(
+            "Getting Started\n===============\n",
+            "入门\n====\n",
+            AnchorStyle::Html,
+            "<a id=\"getting-started\"></a>入门\n====\n",
+        );
+    }
+
+    #[test]
+    fn html_anchors_and_explicit_ids() {
+        assert_pinned(
+            "# Setup\n\n## Usage {#use}\n",
+            "# 安装\n\n## 用法 {#use}\n",
+            AnchorStyle::Html,
+            "# <a id=\"setup\"></a>安装\n\n## 用法 {#use}\n",
+        );
+    }
+
+    #[test]
+    fn mismatched_headings_are_left_alone() {
+        let (output, renamed) = pin_heading_ids("# One\n\n# Two\n", "# 一和二\n", AnchorStyle::Attribute);
+        assert_eq!(output, "# 一和二\n");
+        assert!(renamed.is_empty());
+    }
+
+    #[test]
+    fn fragment_links_point_to_the_source_anchors() {
+        let anchors = HashMap::from([(
+            PathBuf::from("guide/setup.md"),
+            HashMap::from([("安装".to_string(), "setup".to_string())]),
+        )]);
+        let content = "[a](setup.md#安装) [b](../guide/setup.html#安装) [c](#安装) [d](https://x.org/setup.md#安装)\n";
+        assert_eq!(
+            rewrite_fragment_links(content, Path::new("guide/setup.md"), &anchors),
+            "[a](setup.md#setup) [b](../guide/setup.html#setup) [c](#setup) [d](https://x.org/setup.md#安装)\n"
+        );
+    }
+}
@@ -0,0 +1,202 @@
+use anyhow::{Result, anyhow};
+use std::env;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::{BackendKind, TranslatorConfig};
+use crate::error::TranslatorError;
+
+mod anthropic;
+mod command;
+mod deepl;
+mod http;
+mod libretranslate;
+mod mock;
+mod ollama;
+mod openai;
+mod rate_limit;
+#[cfg(test)]
+mod test_server;
+
+pub use anthropic::AnthropicBackend;
+pub use command::CommandBackend;
+pub use deepl::{DeepLBackend, TagHandling};
+pub use http::{HttpClient, RetryPolicy};
+pub use libretranslate::LibreTranslateBackend;
+pub use mock::MockBackend;
+pub use ollama::{OllamaBackend, OllamaEndpoint};
+pub use openai::OpenAiBackend;
+pub use rate_limit::{RateLimitedBackend, RateLimiter};
+pub(crate) use rate_limit::estimate_tokens;
+
+pub(crate) const SYSTEM_PROMPT: &str = "你是专业技术文档翻译助手，保留代码、命令，术语翻译尽量遵循社区的常见用法。如果有不理解的术语，保持原文。文中形如 @@MDT0@@ 的占位符必须原样保留，不要翻译、删除或改动。";
+
+/// 一次翻译请求：待翻译的片段以及翻译所需的上下文
+pub struct TranslationRequest<'a> {
+    pub text: &'a str,
+    /// 源语言，为空时由后端自行识别
+    pub source_lang: &'a str,
+    pub target_lang: &'a str,
+    /// 用户在 book.toml 中配置的额外提示
+    pub prompt: &'a str,
+    /// 片段所在章节的路径，仅作为上下文使用
+    pub chapter_path: &'a str,
+}
+
+/// 翻译服务提供方的抽象，每种 API 对应一个实现。多个线程会同时调用同一个后端
+pub trait TranslationBackend: Send + Sync {
+    fn name(&self) -> &str;
+
+    /// 使用的模型，没有模型概念的后端返回空字符串。与 `name` 一起记录在缓存中，
+    /// 切换模型后可以识别出旧的译文
+    fn model(&self) -> &str {
+        ""
+    }
+
+    fn translate(&self, request: &TranslationRequest) -> Result<String>;
+}
+
+/// 根据 `[preprocessor.translator] backend = "..."` 创建对应的翻译后端，
+/// 配置了每分钟请求数或 token 数时加上限速
+pub fn create_backend(config: &TranslatorConfig, root: &Path) -> Result<Box<dyn TranslationBackend>> {
+    let limiter = (config.max_requests_per_minute.is_some() || config.max_tokens_per_minute.is_some())
+        .then(|| Arc::new(RateLimiter::new(config.max_requests_per_minute, config.max_tokens_per_minute)));
+    let client = || http_client(config, limiter.clone());
+    let base_url = config.base_url();
+    let model = config.model();
+
+    match config.backend {
+        BackendKind::DeepSeek => {
+            let key_env = config.api_key_env().unwrap_or("DEEPSEEK_API_KEY");
+            let api_key = required_env(key_env)?;
+            Ok(Box::new(OpenAiBackend::new(
+                "DeepSeek",
+                client()?,
+                base_url.unwrap_or("https://api.deepseek.com/v1"),
+                model.unwrap_or("deepseek-chat"),
+                Some(api_key),
+            )))
+        }
+        BackendKind::OpenAi => {
+            let model = model.ok_or_else(|| anyhow!("the openai backend requires `model` to be set"))?;
+            // 本地部署的服务通常不需要密钥，只有显式配置了 api-key-env 时才强制要求
+            let api_key = match config.api_key_env() {
+                Some(key_env) => Some(required_env(key_env)?),
+                None => env::var("OPENAI_API_KEY").ok(),
+            };
+            Ok(Box::new(OpenAiBackend::new(
+                "OpenAI-compatible",
+                client()?,
+                base_url.unwrap_or("https://api.openai.com/v1"),
+                model,
+                api_key,
+            )))
+        }
+        BackendKind::Ollama => {
+            let model = model.ok_or_else(|| anyhow!("the ollama backend requires `model` to be set"))?;
+            Ok(Box::new(OllamaBackend::new(
+                client()?,
+                base_url.unwrap_or("http://localhost:11434"),
+                model,
+                config.endpoint,
+            )))
+        }
+        BackendKind::Anthropic => {
+            let model = model.ok_or_else(|| anyhow!("the anthropic backend requires `model` to be set"))?;
+            let api_key = required_env(config.api_key_env().unwrap_or("ANTHROPIC_API_KEY"))?;
+            Ok(Box::new(AnthropicBackend::new(
+                client()?,
+                base_url.unwrap_or("https://api.anthropic.com/v1"),
+                model,
+                api_key,
+                config.max_tokens,
+            )))
+        }
+        BackendKind::DeepL => {
+            let api_key = required_env(config.api_key_env().unwrap_or("DEEPL_API_KEY"))?;
+            Ok(Box::new(DeepLBackend::new(
+                client()?,
+                base_url,
+                api_key,
+                config.tag_handling,
+                config.ignore_tags.clone(),
+                config.glossary_id.as_deref().filter(|id| !id.is_empty()),
+            )))
+        }
+        BackendKind::LibreTranslate => {
+            let api_key = match config.api_key_env() {
+                Some(key_env) => Some(required_env(key_env)?),
+                None => env::var("LIBRETRANSLATE_API_KEY").ok(),
+            };
+            Ok(Box::new(LibreTranslateBackend::new(
+                client()?,
+                base_url.unwrap_or("http://localhost:5000"),
+                api_key,
+            )))
+        }
+        BackendKind::Command => {
+            let command = config.backend_command.as_deref()
+                .filter(|c| !c.is_empty())
+                .ok_or_else(|| anyhow!("the command backend requires `backend-command` to be set"))?;
+            Ok(limited(Box::new(CommandBackend::new(command, root)?), limiter))
+        }
+        BackendKind::Mock => Ok(limited(Box::new(MockBackend), limiter)),
+    }
+}
+
+/// HTTP 后端在 `HttpClient` 中限速，其余后端在每次调用前限速
+fn limited(backend: Box<dyn TranslationBackend>, limiter: Option<Arc<RateLimiter>>) -> Box<dyn TranslationBackend> {
+    match limiter {
+        Some(limiter) => Box::new(RateLimitedBackend::new(backend, limiter)),
+        None => backend,
+    }
+}
+
+fn required_env(key_env: &str) -> Result<String, TranslatorError> {
+    env::var(key_env).map_err(|_| TranslatorError::Auth(format!("environment variable {key_env} is not set")))
+}
+
+/// chat 类接口的一条消息
+pub(crate) struct Message {
+    pub role: &'static str,
+    pub content: String,
+}
+
+/// 构造 chat 类接口共用的消息：系统提示、翻译指令以及用户配置的额外提示
+pub(crate) fn chat_messages(request: &TranslationRequest) -> Vec<Message> {
+    let instruction = if request.source_lang.is_empty() {
+        format!("Translate the following text into {}:\n\n{}", request.target_lang, request.text)
+    } else {
+        format!(
+            "Translate the following {} text into {}:\n\n{}",
+            request.source_lang, request.target_lang, request.text
+        )
+    };
+    let mut messages = Vec::from([
+        Message {
+            role: "system",
+            content: SYSTEM_PROMPT.to_string(),
+        },
+        Message {
+            role: "user",
+            content: instruction,
+        },
+    ]);
+    if !request.prompt.is_empty() {
+        messages.push(Message {
+            role: "user",
+            content: request.prompt.to_string(),
+        });
+    }
+    messages
+}
+
+fn http_client(config: &TranslatorConfig, limiter: Option<Arc<RateLimiter>>) -> Result<HttpClient> {
+    let retry = RetryPolicy {
+        max_retries: config.max_retries,
+        base_delay: Duration::from_secs_f64(config.retry_base_delay),
+        max_delay: Duration::from_secs_f64(config.retry_max_delay),
+    };
+    HttpClient::new(&config.proxy, retry, limiter)
+}
@@ -0,0 +1,97 @@
+use anyhow::{Result, anyhow};
+use serde_json::json;
+
+use super::{HttpClient, TranslationBackend, TranslationRequest, chat_messages, estimate_tokens};
+use crate::error::TranslatorError;
+
+const API_VERSION: &str = "2023-06-01";
+
+/// Anthropic Messages API：system 是顶层字段，回复内容是 content block 数组
+pub struct AnthropicBackend {
+    client: HttpClient,
+    url: String,
+    model: String,
+    api_key: String,
+    max_tokens: u64,
+}
+
+impl AnthropicBackend {
+    pub fn new(client: HttpClient, base_url: &str, model: &str, api_key: String, max_tokens: u64) -> Self {
+        Self {
+            client,
+            url: format!("{}/messages", base_url.trim_end_matches('/')),
+            model: model.to_string(),
+            api_key,
+            max_tokens,
+        }
+    }
+}
+
+impl TranslationBackend for AnthropicBackend {
+    fn name(&self) -> &str {
+        "Anthropic"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn translate(&self, request: &TranslationRequest) -> Result<String> {
+        let messages = chat_messages(request);
+        let system = messages
+            .iter()
+            .filter(|m| m.role == "system")
+            .map(|m| m.content.as_str())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        // Messages API 要求 user/assistant 交替出现，这里把所有 user 消息合并成一条
+        let user = messages
+            .iter()
+            .filter(|m| m.role == "user")
+            .map(|m| m.content.as_str())
+            .collect::<Vec<_>>()
+            .join("\n\n");
+
+        let body = json!({
+            "model": self.model,
+            "max_tokens": self.max_tokens,
+            "system": system,
+            "messages": [{
+                "role": "user",
+                "content": user,
+            }],
+        });
+
+        let headers = [("x-api-key", self.api_key.as_str()), ("anthropic-version", API_VERSION)];
+        let json_resp = self.client.post_json(&self.url, &headers, &body, estimate_tokens(request))?;
+
+        if json_resp["type"] == "error" {
+            return Err(TranslatorError::BadResponse(format!(
+                "anthropic api returned an error: {}",
+                json_resp["error"]["message"].as_str().unwrap_or("unknown error")
+            ))
+            .into());
+        }
+
+        // 输出被 max_tokens 截断时，翻译结果不完整，不能当作正常结果使用
+        if json_resp["stop_reason"] == "max_tokens" {
+            return Err(anyhow!(
+                "translation was truncated after {} tokens, increase `max-tokens` or reduce the chunk size",
+                self.max_tokens
+            ));
+        }
+
+        let translated = json_resp["content"]
+            .as_array()
+            .map(|blocks| {
+                blocks
+                    .iter()
+                    .filter(|block| block["type"] == "text")
+                    .filter_map(|block| block["text"].as_str())
+                    .collect::<String>()
+            })
+            .unwrap_or_default();
+
+        Ok(translated)
+    }
+}
@@ -0,0 +1,71 @@
+use anyhow::{Context, Result, anyhow};
+use serde_json::json;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use super::{TranslationBackend, TranslationRequest};
+
+/// 调用用户配置的外部程序完成翻译：请求以 JSON 写入 stdin，译文从 stdout 读取，
+/// 与 mdBook 通过 `CmdPreprocessor` 调用本插件的方式相同
+pub struct CommandBackend {
+    program: String,
+    args: Vec<String>,
+    root: PathBuf,
+}
+
+impl CommandBackend {
+    pub fn new(command: &str, root: &Path) -> Result<Self> {
+        let mut words = shlex::split(command)
+            .ok_or_else(|| anyhow!("unable to parse `backend-command` {command:?}"))?
+            .into_iter();
+        let program = words
+            .next()
+            .ok_or_else(|| anyhow!("`backend-command` must not be empty"))?;
+        Ok(Self {
+            program,
+            args: words.collect(),
+            root: root.to_path_buf(),
+        })
+    }
+}
+
+impl TranslationBackend for CommandBackend {
+    fn name(&self) -> &str {
+        &self.program
+    }
+
+    fn translate(&self, request: &TranslationRequest) -> Result<String> {
+        let input = json!({
+            "text": request.text,
+            "source_language": request.source_lang,
+            "target_language": request.target_lang,
+            "prompt": request.prompt,
+            "chapter_path": request.chapter_path,
+        });
+
+        let mut child = Command::new(&self.program)
+            .args(&self.args)
+            .current_dir(&self.root)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .with_context(|| format!("unable to start translation command {:?}", self.program))?;
+
+        {
+            let mut stdin = child.stdin.take().expect("child has no stdin");
+            serde_json::to_writer(&mut stdin, &input)?;
+            stdin.flush()?;
+            // stdin 在这里被 drop，子进程才能读到 EOF
+        }
+
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(anyhow!("translation command {:?} exited with {}", self.program, output.status));
+        }
+
+        String::from_utf8(output.stdout)
+            .with_context(|| format!("translation command {:?} wrote invalid UTF-8", self.program))
+    }
+}
@@ -0,0 +1,121 @@
+use anyhow::{Result, anyhow};
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+
+use super::{HttpClient, TranslationBackend, TranslationRequest, estimate_tokens};
+use crate::error::TranslatorError;
+use crate::language::iso_code;
+
+/// 让 DeepL 保留文本中标记的方式，对应 `tag-handling`
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TagHandling {
+    Xml,
+    Html,
+}
+
+impl TagHandling {
+    fn as_str(self) -> &'static str {
+        match self {
+            TagHandling::Xml => "xml",
+            TagHandling::Html => "html",
+        }
+    }
+}
+
+/// DeepL v2 `/translate` 接口
+pub struct DeepLBackend {
+    client: HttpClient,
+    url: String,
+    api_key: String,
+    tag_handling: Option<TagHandling>,
+    ignore_tags: Vec<String>,
+    glossary_id: Option<String>,
+}
+
+impl DeepLBackend {
+    pub fn new(
+        client: HttpClient,
+        base_url: Option<&str>,
+        api_key: String,
+        tag_handling: Option<TagHandling>,
+        ignore_tags: Vec<String>,
+        glossary_id: Option<&str>,
+    ) -> Self {
+        // 免费版的密钥以 ":fx" 结尾，对应不同的域名
+        let base_url = base_url.unwrap_or(if api_key.ends_with(":fx") {
+            "https://api-free.deepl.com/v2"
+        } else {
+            "https://api.deepl.com/v2"
+        });
+        Self {
+            client,
+            url: format!("{}/translate", base_url.trim_end_matches('/')),
+            api_key,
+            tag_handling,
+            ignore_tags,
+            glossary_id: glossary_id.map(str::to_string),
+        }
+    }
+}
+
+/// DeepL 的语言代码是大写的，目标语言需要区分英语和葡萄牙语的变体
+fn deepl_code(language: &str, target: bool) -> Result<String> {
+    let code = iso_code(language)
+        .ok_or_else(|| anyhow!("cannot map language {language:?} to a DeepL language code"))?;
+    let code = match code.as_str() {
+        "en" if target => "en-us".to_string(),
+        "pt" if target => "pt-br".to_string(),
+        "zh" if target => "zh-hans".to_string(),
+        // 源语言只接受不带地区的代码
+        _ if !target => code.split('-').next().unwrap_or_default().to_string(),
+        _ => code,
+    };
+    Ok(code.to_uppercase())
+}
+
+impl TranslationBackend for DeepLBackend {
+    fn name(&self) -> &str {
+        "DeepL"
+    }
+
+    fn translate(&self, request: &TranslationRequest) -> Result<String> {
+        let mut body = Map::new();
+        body.insert("text".to_string(), json!([request.text]));
+        body.insert("target_lang".to_string(), json!(deepl_code(request.target_lang, true)?));
+        body.insert("preserve_formatting".to_string(), json!(true));
+        if !request.source_lang.is_empty() {
+            body.insert("source_lang".to_string(), json!(deepl_code(request.source_lang, false)?));
+        }
+        if let Some(tag_handling) = &self.tag_handling {
+            body.insert("tag_handling".to_string(), json!(tag_handling.as_str()));
+            if !self.ignore_tags.is_empty() {
+                body.insert("ignore_tags".to_string(), json!(self.ignore_tags));
+            }
+        }
+        if let Some(glossary_id) = &self.glossary_id {
+            // DeepL 要求使用术语表时必须指定源语言
+            if request.source_lang.is_empty() {
+                return Err(anyhow!("`glossary-id` requires `source-language` to be set"));
+            }
+            body.insert("glossary_id".to_string(), json!(glossary_id));
+        }
+
+        let authorization = format!("DeepL-Auth-Key {}", self.api_key);
+        let json_resp = self.client.post_json(
+            &self.url,
+            &[("Authorization", &authorization)],
+            &Value::Object(body),
+            estimate_tokens(request),
+        )?;
+
+        if let Some(message) = json_resp["message"].as_str() {
+            return Err(TranslatorError::BadResponse(format!("deepl api returned an error: {message}")).into());
+        }
+
+        Ok(json_resp["translations"][0]["text"]
+            .as_str()
+            .unwrap_or("")
+            .to_string())
+    }
+}
@@ -0,0 +1,297 @@
+use anyhow::Result;
+use reqwest::StatusCode;
+use reqwest::blocking::{Client, Response};
+use reqwest::header::RETRY_AFTER;
+use serde_json::Value;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use super::RateLimiter;
+use crate::error::TranslatorError;
+
+/// 请求失败后的重试策略，对应 `max-retries`、`retry-base-delay` 和 `retry-max-delay`
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    /// 第一次重试前的等待时间，之后每次翻倍
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// 第 `attempt` 次重试前的等待时间：指数退避，并在后一半范围内随机抖动，
+    /// 避免多个请求同时失败后又同时重试
+    fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        delay / 2 + delay.mul_f64(fastrand::f64() / 2.0)
+    }
+}
+
+/// 一次请求失败的原因，决定是否值得重试
+enum Failure {
+    /// 超时、连接中断、429、5xx 等暂时性错误；服务端给出 `Retry-After` 时按它等待。
+    /// 重试用尽后由 `kind` 生成最终的错误
+    Retryable {
+        kind: fn(String) -> TranslatorError,
+        message: String,
+        retry_after: Option<Duration>,
+    },
+    /// 密钥错误、请求格式错误等，重试也不会成功
+    Fatal(TranslatorError),
+}
+
+/// 各 HTTP 后端共用的客户端，暂时性错误按 `RetryPolicy` 自动重试
+pub struct HttpClient {
+    client: Client,
+    retry: RetryPolicy,
+    /// 配置了限速时，每次发出请求（包括重试）前都要取得额度
+    limiter: Option<Arc<RateLimiter>>,
+}
+
+impl HttpClient {
+    pub fn new(proxy: &str, retry: RetryPolicy, limiter: Option<Arc<RateLimiter>>) -> Result<Self> {
+        let mut client_builder = Client::builder()
+            .timeout(Duration::from_secs(600)); // 显式设置超时
+
+        if !proxy.is_empty() {
+            client_builder = client_builder.proxy(reqwest::Proxy::all(proxy)?);
+        }
+
+        Ok(Self {
+            client: client_builder.build()?,
+            retry,
+            limiter,
+        })
+    }
+
+    /// POST 一个 JSON 请求并解析 JSON 响应，非 2xx 状态码视为错误。
+    /// `tokens` 是这次请求预计消耗的 token 数，用于限速
+    pub fn post_json(
+        &self,
+        url: &str,
+        headers: &[(&str, &str)],
+        body: &Value,
+        tokens: u64,
+    ) -> Result<Value, TranslatorError> {
+        let mut attempt = 0;
+        loop {
+            if let Some(limiter) = &self.limiter {
+                limiter.acquire(tokens);
+            }
+            let (kind, message, retry_after) = match self.try_post_json(url, headers, body) {
+                Ok(value) => return Ok(value),
+                Err(Failure::Fatal(error)) => return Err(error),
+                Err(Failure::Retryable { kind, message, retry_after }) => (kind, message, retry_after),
+            };
+            if attempt >= self.retry.max_retries {
+                return Err(kind(format!("{message} (gave up after {} attempts)", attempt + 1)));
+            }
+
+            // 服务端要求的等待时间同样不超过 `retry-max-delay`，避免一个很大的 Retry-After 卡住构建
+            let delay = retry_after.map_or_else(|| self.retry.backoff(attempt), |d| d.min(self.retry.max_delay));
+            attempt += 1;
+            eprintln!(
+                "\x1b[33;1mWarning:\x1b[0m {message}, retrying in {:.1}s ({attempt}/{})",
+                delay.as_secs_f64(),
+                self.retry.max_retries
+            );
+            thread::sleep(delay);
+        }
+    }
+
+    fn try_post_json(&self, url: &str, headers: &[(&str, &str)], body: &Value) -> Result<Value, Failure> {
+        let mut request = self.client.post(url).json(body);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+
+        let response = request.send().map_err(|e| {
+            let message = format!("request to {url} failed: {e}");
+            if e.is_timeout() || e.is_connect() || e.is_request() || e.is_body() {
+                Failure::Retryable { kind: TranslatorError::Network, message, retry_after: None }
+            } else {
+                Failure::Fatal(TranslatorError::Network(message))
+            }
+        })?;
+
+        let status = response.status();
+        let retry_after = retry_after(&response);
+        // 响应体读到一半连接断开同样值得重试
+        let text = response.text().map_err(|e| Failure::Retryable {
+            kind: TranslatorError::Network,
+            message: format!("failed to read the response from {url}: {e}"),
+            retry_after: None,
+        })?;
+
+        if !status.is_success() {
+            let message = format!("{url} returned {status}: {}", preview(&text));
+            return Err(match status {
+                StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Failure::Fatal(TranslatorError::Auth(message)),
+                StatusCode::TOO_MANY_REQUESTS => Failure::Retryable {
+                    kind: TranslatorError::RateLimited,
+                    message,
+                    retry_after,
+                },
+                status if is_retryable(status) => Failure::Retryable {
+                    kind: TranslatorError::BadResponse,
+                    message,
+                    retry_after,
+                },
+                _ => Failure::Fatal(TranslatorError::BadResponse(message)),
+            });
+        }
+
+        serde_json::from_str(&text).map_err(|e| {
+            Failure::Fatal(TranslatorError::BadResponse(format!(
+                "invalid JSON response from {url}: {e}: {}",
+                preview(&text)
+            )))
+        })
+    }
+}
+
+/// 408 和 5xx（含 Anthropic 过载时返回的 529）是暂时性的，其余 4xx 重试也不会成功
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::REQUEST_TIMEOUT || status.is_server_error()
+}
+
+/// 解析 `Retry-After`，支持秒数和 HTTP 日期两种格式
+fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(date.duration_since(SystemTime::now()).unwrap_or_default())
+}
+
+/// 错误信息中只保留响应体的前 200 个字符
+fn preview(text: &str) -> String {
+    let text = text.trim();
+    if text.chars().count() > 200 {
+        format!("{}...", text.chars().take(200).collect::<String>())
+    } else {
+        text.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::test_server::{TestServer, reply};
+    use serde_json::json;
+
+    fn post(server: &TestServer, max_retries: u32) -> Result<Value, TranslatorError> {
+        post_with_max_delay(server, max_retries, Duration::from_millis(20))
+    }
+
+    fn post_with_max_delay(server: &TestServer, max_retries: u32, max_delay: Duration) -> Result<Value, TranslatorError> {
+        let retry = RetryPolicy {
+            max_retries,
+            base_delay: Duration::from_millis(10),
+            max_delay,
+        };
+        let client = HttpClient::new("", retry, None).unwrap();
+        let url = format!("{}/v1/chat/completions", server.url);
+        client.post_json(&url, &[("Authorization", "Bearer key")], &json!({ "text": "Hello" }), 0)
+    }
+
+    #[test]
+    fn retries_server_errors() {
+        let server = TestServer::start(vec![
+            reply(503, r#"{"error":"overloaded"}"#),
+            reply(500, "Internal Server Error"),
+            reply(200, r#"{"ok":true}"#),
+        ]);
+        assert_eq!(post(&server, 5).unwrap(), json!({ "ok": true }));
+
+        let received = server.received();
+        assert_eq!(received.len(), 3);
+        assert!(received.iter().all(|r| r.path == "/v1/chat/completions" && r.body == json!({ "text": "Hello" })));
+        assert!(received[0].headers.contains(&("authorization".to_string(), "Bearer key".to_string())));
+    }
+
+    #[test]
+    fn honours_retry_after() {
+        let server = TestServer::start(vec![
+            reply(429, r#"{"error":"slow down"}"#).header("Retry-After", "1"),
+            reply(200, r#"{"ok":true}"#),
+        ]);
+        assert!(post_with_max_delay(&server, 5, Duration::from_secs(8)).is_ok());
+
+        let received = server.received();
+        assert_eq!(received.len(), 2);
+        assert!(received[1].at - received[0].at >= Duration::from_secs(1));
+    }
+
+    #[test]
+    fn caps_retry_after_at_max_delay() {
+        let server = TestServer::start(vec![
+            reply(429, r#"{"error":"slow down"}"#).header("Retry-After", "3600"),
+            reply(503, "busy").header("Retry-After", "Fri, 31 Dec 9999 23:59:59 GMT"),
+            reply(200, r#"{"ok":true}"#),
+        ]);
+        assert!(post(&server, 5).is_ok());
+
+        let received = server.received();
+        assert_eq!(received.len(), 3);
+        assert!(received[2].at - received[0].at < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn gives_up_after_max_retries() {
+        let server = TestServer::start(vec![reply(502, "bad gateway"), reply(502, "bad gateway"), reply(502, "bad gateway")]);
+        let error = post(&server, 2).unwrap_err();
+        assert!(
+            matches!(&error, TranslatorError::BadResponse(message) if message.contains("gave up after 3 attempts")),
+            "{error}"
+        );
+        assert_eq!(server.received().len(), 3);
+
+        let server = TestServer::start(vec![reply(429, "slow down"), reply(429, "slow down")]);
+        assert!(matches!(post(&server, 1), Err(TranslatorError::RateLimited(_))));
+    }
+
+    #[test]
+    fn does_not_retry_client_errors() {
+        let server = TestServer::start(vec![reply(401, r#"{"error":"invalid key"}"#), reply(200, "{}")]);
+        assert!(matches!(post(&server, 5), Err(TranslatorError::Auth(message)) if message.contains("invalid key")));
+        assert_eq!(server.received().len(), 1);
+
+        let server = TestServer::start(vec![reply(400, r#"{"error":"bad request"}"#), reply(200, "{}")]);
+        assert!(matches!(post(&server, 5), Err(TranslatorError::BadResponse(_))));
+        assert_eq!(server.received().len(), 1);
+
+        let server = TestServer::start(vec![reply(200, "not json"), reply(200, "{}")]);
+        assert!(matches!(post(&server, 5), Err(TranslatorError::BadResponse(message)) if message.contains("invalid JSON")));
+        assert_eq!(server.received().len(), 1);
+    }
+
+    #[test]
+    fn parses_retry_after_dates() {
+        let server = TestServer::start(vec![
+            reply(503, "busy").header("Retry-After", "Wed, 21 Oct 2015 07:28:00 GMT"),
+            reply(200, "{}"),
+        ]);
+        // 已经过去的日期不需要等待
+        assert!(post(&server, 1).is_ok());
+    }
+
+    #[test]
+    fn backoff_grows_and_is_capped() {
+        let retry = RetryPolicy {
+            max_retries: 10,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(8),
+        };
+        for (attempt, full) in [(0, 1), (1, 2), (2, 4), (3, 8), (9, 8)] {
+            let delay = retry.backoff(attempt);
+            let full = Duration::from_secs(full);
+            assert!(delay >= full / 2 && delay <= full, "attempt {attempt}: {delay:?}");
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use anyhow::{Result, anyhow};
+use serde_json::json;
+
+use super::{HttpClient, TranslationBackend, TranslationRequest, estimate_tokens};
+use crate::error::TranslatorError;
+use crate::language::iso_code;
+
+/// 自建的 LibreTranslate 服务
+pub struct LibreTranslateBackend {
+    client: HttpClient,
+    url: String,
+    api_key: Option<String>,
+}
+
+impl LibreTranslateBackend {
+    pub fn new(client: HttpClient, base_url: &str, api_key: Option<String>) -> Self {
+        Self {
+            client,
+            url: format!("{}/translate", base_url.trim_end_matches('/')),
+            api_key,
+        }
+    }
+}
+
+fn libre_code(language: &str) -> Result<String> {
+    let code = iso_code(language)
+        .ok_or_else(|| anyhow!("cannot map language {language:?} to a LibreTranslate language code"))?;
+    // LibreTranslate 用 "zh-Hant" 表示繁体中文，其余语言只使用两位代码
+    Ok(match code.as_str() {
+        "zh-hant" => "zh-Hant".to_string(),
+        _ => code.split('-').next().unwrap_or_default().to_string(),
+    })
+}
+
+impl TranslationBackend for LibreTranslateBackend {
+    fn name(&self) -> &str {
+        "LibreTranslate"
+    }
+
+    fn translate(&self, request: &TranslationRequest) -> Result<String> {
+        let source = if request.source_lang.is_empty() {
+            "auto".to_string()
+        } else {
+            libre_code(request.source_lang)?
+        };
+        let mut body = json!({
+            "q": request.text,
+            "source": source,
+            "target": libre_code(request.target_lang)?,
+            "format": "text",
+        });
+        if let Some(api_key) = &self.api_key {
+            body["api_key"] = json!(api_key);
+        }
+
+        let json_resp = self.client.post_json(&self.url, &[], &body, estimate_tokens(request))?;
+
+        if let Some(error) = json_resp["error"].as_str() {
+            return Err(TranslatorError::BadResponse(format!("libretranslate returned an error: {error}")).into());
+        }
+
+        Ok(json_resp["translatedText"]
+            .as_str()
+            .unwrap_or("")
+            .to_string())
+    }
+}
@@ -0,0 +1,156 @@
+use anyhow::Result;
+
+use super::{TranslationBackend, TranslationRequest};
+use crate::protect::{placeholder_len, strip_placeholders};
+
+/// 不访问网络的伪本地化后端：给文字加上重音符号、用方括号包起来并把长度扩展约 30%，
+/// 结果是确定的，适合测试和在付费翻译之前检查主题的排版
+pub struct MockBackend;
+
+impl TranslationBackend for MockBackend {
+    fn name(&self) -> &str {
+        "Mock"
+    }
+
+    fn translate(&self, request: &TranslationRequest) -> Result<String> {
+        Ok(pseudo_localize(request.text))
+    }
+}
+
+fn pseudo_localize(text: &str) -> String {
+    let mut output = String::with_capacity(text.len() * 2);
+    let mut fence: Option<&str> = None;
+    let mut indented_code = false;
+    let mut prev_blank = true;
+
+    for line in text.split_inclusive('\n') {
+        let (body, ending) = match line.strip_suffix('\n') {
+            Some(body) => (body, "\n"),
+            None => (line, ""),
+        };
+        let trimmed = body.trim_start();
+
+        // 代码块原样保留
+        if let Some(marker) = fence {
+            if trimmed.starts_with(marker) {
+                fence = None;
+            }
+            output.push_str(line);
+            continue;
+        }
+        if let Some(marker) = ["```", "~~~"].into_iter().find(|m| trimmed.starts_with(m)) {
+            fence = Some(marker);
+            output.push_str(line);
+            continue;
+        }
+
+        // 空行之后缩进四格的是缩进代码块
+        let indented = body.starts_with("    ") || body.starts_with('\t');
+        indented_code = indented && (indented_code || prev_blank);
+        prev_blank = trimmed.is_empty();
+        if indented_code {
+            output.push_str(line);
+            continue;
+        }
+
+        if !strip_placeholders(body).chars().any(char::is_alphabetic) || trimmed.starts_with('<') || trimmed.starts_with("{{#") {
+            output.push_str(line);
+        } else if trimmed.starts_with('|') {
+            // 表格行加括号会破坏表格结构，只替换字母
+            output.push_str(&accent(body));
+            output.push_str(ending);
+        } else {
+            let (prefix, content) = body.split_at(block_prefix_len(body));
+            let (content, attrs) = match content.rfind(" {#") {
+                Some(pos) if content.ends_with('}') => content.split_at(pos),
+                _ => (content, ""),
+            };
+            let padding = (content.chars().count() * 3).div_ceil(10);
+            output.push_str(prefix);
+            output.push('[');
+            output.push_str(&accent(content));
+            output.push(' ');
+            output.extend(std::iter::repeat_n('·', padding));
+            output.push(']');
+            output.push_str(attrs);
+            output.push_str(ending);
+        }
+    }
+
+    output
+}
+
+/// 行首的 Markdown 块标记（缩进、标题、引用、列表、任务框）的字节长度
+fn block_prefix_len(line: &str) -> usize {
+    let mut rest = line;
+    loop {
+        let before = rest.len();
+        rest = rest.trim_start();
+        if let Some(stripped) = rest.strip_prefix('>') {
+            rest = stripped;
+        } else if rest.starts_with('#') {
+            rest = rest.trim_start_matches('#');
+        } else if let Some(stripped) = ["- ", "* ", "+ ", "[ ] ", "[x] ", "[X] "]
+            .into_iter()
+            .find_map(|m| rest.strip_prefix(m))
+        {
+            rest = stripped;
+        } else {
+            let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            if digits > 0
+                && let Some(stripped) = rest[digits..]
+                    .strip_prefix(". ")
+                    .or_else(|| rest[digits..].strip_prefix(") "))
+            {
+                rest = stripped;
+            }
+        }
+        if rest.len() == before {
+            return line.len() - rest.len();
+        }
+    }
+}
+
+/// 替换普通文字中的字母，跳过行内代码、链接地址、HTML 标签和 mdBook 指令
+fn accent(text: &str) -> String {
+    let mut output = String::with_capacity(text.len() * 2);
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        let verbatim_end = match c {
+            '`' => text[i + 1..].find('`').map(|end| i + 1 + end + 1),
+            '<' => text[i..].find('>').map(|end| i + end + 1),
+            '(' if text[..i].ends_with(']') => text[i..].find(')').map(|end| i + end + 1),
+            '{' if text[i..].starts_with("{{#") => text[i..].find("}}").map(|end| i + end + 2),
+            '@' => placeholder_len(&text[i..]).map(|len| i + len),
+            _ => None,
+        };
+        if let Some(end) = verbatim_end {
+            output.push_str(&text[i..end]);
+            while chars.next_if(|(j, _)| *j < end).is_some() {}
+            continue;
+        }
+
+        output.push(match c {
+            'a' => 'á',
+            'e' => 'é',
+            'i' => 'í',
+            'o' => 'ó',
+            'u' => 'ú',
+            'y' => 'ý',
+            'c' => 'ç',
+            'n' => 'ñ',
+            'A' => 'Á',
+            'E' => 'É',
+            'I' => 'Í',
+            'O' => 'Ó',
+            'U' => 'Ú',
+            'Y' => 'Ý',
+            'C' => 'Ç',
+            'N' => 'Ñ',
+            other => other,
+        });
+    }
+
+    output
+}
@@ -0,0 +1,168 @@
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::{HttpClient, TranslationBackend, TranslationRequest, chat_messages, estimate_tokens};
+use crate::error::TranslatorError;
+
+/// Ollama 提供的两种接口，对应 `endpoint = "chat" | "generate"`
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OllamaEndpoint {
+    /// `/api/chat`，与 chat-completions 类似的多轮消息
+    Chat,
+    /// `/api/generate`，单个 prompt 加上 system 字段
+    Generate,
+}
+
+/// 本地 Ollama 服务，用于无法访问外网的环境
+pub struct OllamaBackend {
+    client: HttpClient,
+    host: String,
+    model: String,
+    endpoint: OllamaEndpoint,
+}
+
+impl OllamaBackend {
+    pub fn new(client: HttpClient, host: &str, model: &str, endpoint: OllamaEndpoint) -> Self {
+        Self {
+            client,
+            host: host.trim_end_matches('/').to_string(),
+            model: model.to_string(),
+            endpoint,
+        }
+    }
+}
+
+impl TranslationBackend for OllamaBackend {
+    fn name(&self) -> &str {
+        "Ollama"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn translate(&self, request: &TranslationRequest) -> Result<String> {
+        let messages = chat_messages(request);
+
+        let (url, body) = match self.endpoint {
+            OllamaEndpoint::Chat => (
+                format!("{}/api/chat", self.host),
+                json!({
+                    "model": self.model,
+                    "stream": false,
+                    "messages": messages.iter().map(|m| json!({
+                        "role": m.role,
+                        "content": m.content,
+                    })).collect::<Vec<_>>(),
+                }),
+            ),
+            OllamaEndpoint::Generate => {
+                let system = messages
+                    .iter()
+                    .filter(|m| m.role == "system")
+                    .map(|m| m.content.as_str())
+                    .collect::<Vec<_>>()
+                    .join("\n\n");
+                let prompt = messages
+                    .iter()
+                    .filter(|m| m.role != "system")
+                    .map(|m| m.content.as_str())
+                    .collect::<Vec<_>>()
+                    .join("\n\n");
+                (
+                    format!("{}/api/generate", self.host),
+                    json!({
+                        "model": self.model,
+                        "stream": false,
+                        "system": system,
+                        "prompt": prompt,
+                    }),
+                )
+            }
+        };
+
+        let json_resp = self.client.post_json(&url, &[], &body, estimate_tokens(request))?;
+
+        if let Some(error) = json_resp["error"].as_str() {
+            return Err(TranslatorError::BadResponse(format!("ollama returned an error: {error}")).into());
+        }
+
+        let content = match self.endpoint {
+            OllamaEndpoint::Chat => &json_resp["message"]["content"],
+            OllamaEndpoint::Generate => &json_resp["response"],
+        };
+        Ok(content.as_str().unwrap_or("").to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::RetryPolicy;
+    use crate::backend::test_server::{TestServer, reply};
+    use std::time::Duration;
+
+    fn backend(server: &TestServer, endpoint: OllamaEndpoint) -> OllamaBackend {
+        let retry = RetryPolicy {
+            max_retries: 0,
+            base_delay: Duration::ZERO,
+            max_delay: Duration::ZERO,
+        };
+        let client = HttpClient::new("", retry, None).unwrap();
+        OllamaBackend::new(client, &format!("{}/", server.url), "llama3", endpoint)
+    }
+
+    fn request() -> TranslationRequest<'static> {
+        TranslationRequest {
+            text: "Hello",
+            source_lang: "",
+            target_lang: "Chinese",
+            prompt: "Keep it short.",
+            chapter_path: "intro.md",
+        }
+    }
+
+    #[test]
+    fn chat_endpoint() {
+        let server = TestServer::start(vec![reply(200, r#"{"message":{"role":"assistant","content":"你好"}}"#)]);
+        let translated = backend(&server, OllamaEndpoint::Chat).translate(&request()).unwrap();
+        assert_eq!(translated, "你好");
+
+        let received = server.received();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].path, "/api/chat");
+        assert!(received[0].headers.contains(&("content-type".to_string(), "application/json".to_string())));
+        let body = &received[0].body;
+        assert_eq!(body["model"], "llama3");
+        assert_eq!(body["stream"], false);
+        let roles = body["messages"].as_array().unwrap().iter().map(|m| m["role"].as_str().unwrap()).collect::<Vec<_>>();
+        assert_eq!(roles, ["system", "user", "user"]);
+        assert!(body["messages"][1]["content"].as_str().unwrap().ends_with("into Chinese:\n\nHello"));
+        assert_eq!(body["messages"][2]["content"], "Keep it short.");
+    }
+
+    #[test]
+    fn generate_endpoint() {
+        let server = TestServer::start(vec![reply(200, r#"{"response":"你好","done":true}"#)]);
+        let translated = backend(&server, OllamaEndpoint::Generate).translate(&request()).unwrap();
+        assert_eq!(translated, "你好");
+
+        let received = server.received();
+        assert_eq!(received[0].path, "/api/generate");
+        let body = &received[0].body;
+        assert_eq!(body["system"], crate::backend::SYSTEM_PROMPT);
+        assert!(body["prompt"].as_str().unwrap().ends_with("Hello\n\nKeep it short."));
+    }
+
+    #[test]
+    fn error_in_the_response_body() {
+        let server = TestServer::start(vec![reply(200, r#"{"error":"model \"llama3\" not found"}"#)]);
+        let error = backend(&server, OllamaEndpoint::Chat).translate(&request()).unwrap_err();
+        assert!(matches!(
+            TranslatorError::from_backend(error),
+            TranslatorError::BadResponse(message) if message.contains("not found")
+        ));
+    }
+}
@@ -0,0 +1,57 @@
+use anyhow::Result;
+use serde_json::json;
+
+use super::{HttpClient, TranslationBackend, TranslationRequest, chat_messages, estimate_tokens};
+
+/// 兼容 OpenAI chat-completions 协议的后端，DeepSeek、vLLM、LM Studio、OpenRouter 等均可使用
+pub struct OpenAiBackend {
+    name: String,
+    client: HttpClient,
+    url: String,
+    model: String,
+    api_key: Option<String>,
+}
+
+impl OpenAiBackend {
+    pub fn new(name: &str, client: HttpClient, base_url: &str, model: &str, api_key: Option<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            client,
+            url: format!("{}/chat/completions", base_url.trim_end_matches('/')),
+            model: model.to_string(),
+            api_key,
+        }
+    }
+}
+
+impl TranslationBackend for OpenAiBackend {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn translate(&self, request: &TranslationRequest) -> Result<String> {
+        let body = json!({
+            "model": self.model,
+            "messages": chat_messages(request).iter().map(|m| json!({
+                "role": m.role,
+                "content": m.content,
+            })).collect::<Vec<_>>(),
+        });
+
+        let authorization = self.api_key.as_ref().map(|api_key| format!("Bearer {}", api_key));
+        let headers = match &authorization {
+            Some(value) => vec![("Authorization", value.as_str())],
+            None => Vec::new(),
+        };
+        let json_resp = self.client.post_json(&self.url, &headers, &body, estimate_tokens(request))?;
+
+        Ok(json_resp["choices"][0]["message"]["content"]
+            .as_str()
+            .unwrap_or("")
+            .to_string())
+    }
+}
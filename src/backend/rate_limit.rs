@@ -0,0 +1,193 @@
+use anyhow::Result;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::{SYSTEM_PROMPT, TranslationBackend, TranslationRequest};
+
+/// 按 `max-requests-per-minute` 和 `max-tokens-per-minute` 限速，多个线程共用同一组令牌桶。
+/// HTTP 后端由 `HttpClient` 在每次发出请求（包括重试）前调用，其余后端由 `RateLimitedBackend` 调用
+pub struct RateLimiter {
+    buckets: Mutex<Buckets>,
+}
+
+/// 为不经过 `HttpClient` 的后端（如外部命令）在每次调用前限速
+pub struct RateLimitedBackend {
+    inner: Box<dyn TranslationBackend>,
+    limiter: Arc<RateLimiter>,
+}
+
+struct Buckets {
+    requests: Option<TokenBucket>,
+    tokens: Option<TokenBucket>,
+    /// 是否已经提示过单个请求超出每分钟的 token 额度
+    warned_oversized: bool,
+}
+
+/// 容量为每分钟的额度、按秒匀速补充的令牌桶。余额可以为负，
+/// 欠下的额度由之后的调用者按顺序等待补齐，先到的请求先发出
+struct TokenBucket {
+    capacity: f64,
+    available: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(per_minute: u64) -> Self {
+        Self {
+            capacity: per_minute as f64,
+            available: per_minute as f64,
+            updated: Instant::now(),
+        }
+    }
+
+    /// 取走 `amount` 个令牌，返回需要等待的时间
+    fn take(&mut self, amount: f64, now: Instant) -> Duration {
+        let per_second = self.capacity / 60.0;
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.available = (self.available + elapsed * per_second).min(self.capacity);
+        self.updated = now;
+
+        self.available -= amount;
+        if self.available >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.available / per_second)
+        }
+    }
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_minute: Option<u64>, tokens_per_minute: Option<u64>) -> Self {
+        Self {
+            buckets: Mutex::new(Buckets {
+                requests: requests_per_minute.map(TokenBucket::new),
+                tokens: tokens_per_minute.map(TokenBucket::new),
+                warned_oversized: false,
+            }),
+        }
+    }
+
+    /// 为一次请求预留额度，并等待到额度足够为止
+    pub fn acquire(&self, tokens: u64) {
+        let now = Instant::now();
+        let wait = {
+            let mut buckets = self.buckets.lock().unwrap_or_else(PoisonError::into_inner);
+            // 超出额度的请求仍然会发出，只是每次都要等上一分钟以上，只提示一次
+            if let Some(bucket) = &buckets.tokens
+                && tokens as f64 > bucket.capacity
+                && !buckets.warned_oversized
+            {
+                eprintln!(
+                    "\x1b[33;1mWarning:\x1b[0m a request is estimated at {tokens} tokens, more than max-tokens-per-minute ({}); \
+                     each such request waits for the quota to refill",
+                    bucket.capacity
+                );
+                buckets.warned_oversized = true;
+            }
+            let request_wait = buckets.requests.as_mut().map_or(Duration::ZERO, |b| b.take(1.0, now));
+            let token_wait = buckets.tokens.as_mut().map_or(Duration::ZERO, |b| b.take(tokens as f64, now));
+            request_wait.max(token_wait)
+        };
+
+        if wait >= Duration::from_secs(1) {
+            eprintln!(
+                "\x1b[38;2;214;200;75;1mRate limit reached, waiting {:.1}s\x1b[0m",
+                wait.as_secs_f64()
+            );
+        }
+        thread::sleep(wait);
+    }
+}
+
+impl RateLimitedBackend {
+    pub fn new(inner: Box<dyn TranslationBackend>, limiter: Arc<RateLimiter>) -> Self {
+        Self { inner, limiter }
+    }
+}
+
+impl TranslationBackend for RateLimitedBackend {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+
+    fn translate(&self, request: &TranslationRequest) -> Result<String> {
+        self.limiter.acquire(estimate_tokens(request));
+        self.inner.translate(request)
+    }
+}
+
+/// 粗略估算一次请求消耗的 token：系统提示、用户提示和原文，再加上与原文等长的译文。
+/// 英文等文字约 4 个字符一个 token，中日韩等非 ASCII 字符按每字一个 token 计算
+pub(crate) fn estimate_tokens(request: &TranslationRequest) -> u64 {
+    let count = |text: &str| {
+        let ascii = text.bytes().filter(u8::is_ascii).count() as u64;
+        let other = text.chars().filter(|c| !c.is_ascii()).count() as u64;
+        ascii.div_ceil(4) + other
+    };
+    count(SYSTEM_PROMPT) + count(request.prompt) + 2 * count(request.text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bucket(per_minute: u64, start: Instant) -> TokenBucket {
+        TokenBucket {
+            updated: start,
+            ..TokenBucket::new(per_minute)
+        }
+    }
+
+    #[test]
+    fn limits_requests_per_minute() {
+        let start = Instant::now();
+        let mut requests = bucket(60, start);
+        // 初始额度可以立即用完，之后每秒补充一个请求
+        for _ in 0..60 {
+            assert_eq!(requests.take(1.0, start), Duration::ZERO);
+        }
+        assert_eq!(requests.take(1.0, start), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn refills_tokens_over_time() {
+        let start = Instant::now();
+        let mut tokens = bucket(6000, start);
+        assert_eq!(tokens.take(6000.0, start), Duration::ZERO);
+        // 每秒补充 100 个，10 秒后可以再用 1000 个
+        assert_eq!(tokens.take(1000.0, start + Duration::from_secs(10)), Duration::ZERO);
+        assert_eq!(tokens.take(500.0, start + Duration::from_secs(10)), Duration::from_secs(5));
+        // 补充不会超过容量
+        let mut idle = bucket(6000, start);
+        idle.take(6000.0, start);
+        assert_eq!(idle.take(6000.0, start + Duration::from_secs(3600)), Duration::ZERO);
+        assert_eq!(idle.take(100.0, start + Duration::from_secs(3600)), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn queues_later_callers_behind_a_negative_balance() {
+        let start = Instant::now();
+        let mut requests = bucket(60, start);
+        requests.take(60.0, start);
+        // 同一时刻到达的调用者依次排在前一个之后
+        let waits = (0..3).map(|_| requests.take(1.0, start)).collect::<Vec<_>>();
+        assert_eq!(waits, [Duration::from_secs(1), Duration::from_secs(2), Duration::from_secs(3)]);
+        // 第一个调用者等待结束时，后面的调用者仍然要等
+        assert_eq!(requests.take(1.0, start + Duration::from_secs(1)), Duration::from_secs(3));
+    }
+
+    #[test]
+    fn oversized_requests_wait_for_the_quota_to_refill() {
+        let start = Instant::now();
+        let mut tokens = bucket(1200, start);
+        // 一个请求要用掉两分钟的额度，每个这样的请求都要多等一分钟
+        assert_eq!(tokens.take(2400.0, start), Duration::from_secs(60));
+        assert_eq!(tokens.take(2400.0, start), Duration::from_secs(180));
+        assert_eq!(tokens.take(20.0, start + Duration::from_secs(60)), Duration::from_secs(121));
+    }
+}
@@ -0,0 +1,249 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::backend::TranslationBackend;
+use crate::config::TranslatorConfig;
+use crate::error::TranslatorError;
+use crate::language::iso_code;
+
+mod json;
+mod sqlite;
+
+pub use json::JsonStore;
+pub use sqlite::SqliteStore;
+
+/// 缓存的存储方式，对应 `cache-store = "json" | "sqlite"`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StoreKind {
+    Json,
+    Sqlite,
+}
+
+impl StoreKind {
+    pub fn extension(self) -> &'static str {
+        match self {
+            StoreKind::Json => "json",
+            StoreKind::Sqlite => "sqlite",
+        }
+    }
+
+    /// 根据缓存文件的扩展名判断存储方式
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "json" => Some(StoreKind::Json),
+            "sqlite" => Some(StoreKind::Sqlite),
+            _ => None,
+        }
+    }
+}
+
+/// 存储中读出的一条译文
+pub struct StoredEntry {
+    pub translation: String,
+    pub fingerprint: Option<String>,
+}
+
+/// 写入存储的一条译文及其来源
+pub struct NewEntry<'a> {
+    pub source: &'a str,
+    pub translation: &'a str,
+    pub language: &'a str,
+    pub backend: &'a str,
+    pub model: &'a str,
+    pub fingerprint: &'a str,
+}
+
+/// 导出和导入时的一条缓存记录，对应 JSON Lines 中的一行。JSON 缓存没有记录的信息为 `None`
+#[derive(Serialize, Deserialize)]
+pub struct CacheRecord {
+    pub key: String,
+    pub translation: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backend: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hits: Option<u64>,
+}
+
+/// 缓存的存储后端，每种存储方式对应一个实现。并发翻译时由 `Mutex` 保护，只需要 `Send`
+pub trait CacheStore: Send {
+    fn get(&self, key: &str) -> Result<Option<StoredEntry>, TranslatorError>;
+
+    /// 写入一条译文，调用返回时已经持久化
+    fn put(&mut self, key: &str, entry: &NewEntry) -> Result<(), TranslatorError>;
+
+    /// 记录一次缓存命中
+    fn record_hit(&mut self, key: &str) -> Result<(), TranslatorError>;
+
+    /// 全部条目，按 key 排序
+    fn records(&self) -> Result<Vec<CacheRecord>, TranslatorError>;
+
+    /// 批量写入条目，已有的 key 会被覆盖
+    fn import(&mut self, records: Vec<CacheRecord>) -> Result<(), TranslatorError>;
+
+    fn remove(&mut self, keys: &[String]) -> Result<(), TranslatorError>;
+
+    /// 存储本身的完整性问题，如数据库文件损坏
+    fn check(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// 一种目标语言的翻译缓存，保存在 `<book root>/<cache-dir>/<language>.json`（或 `.sqlite`）
+pub struct Cache {
+    path: PathBuf,
+    store: Box<dyn CacheStore>,
+    /// 持有期间其他构建进程无法打开同一个缓存，随 `Cache` 一起释放
+    _lock: File,
+    language: String,
+    backend: String,
+    model: String,
+    /// 当前配置的指纹，写入的条目都带上它
+    fingerprint: String,
+    /// 为 `true` 时指纹不同的条目视为未缓存，重新翻译
+    invalidate_stale: bool,
+    /// 命中的指纹不同的条目数
+    stale: usize,
+}
+
+impl Cache {
+    /// 缓存文件的位置：`cache_dir` 为相对路径时相对于书籍根目录，文件名为目标语言的代码，
+    /// 如 `.translator-cache/zh.json`，每种语言可以单独提交或清理
+    pub fn path_for(root: &Path, cache_dir: &str, language: &str, store: StoreKind) -> PathBuf {
+        root.join(cache_dir)
+            .join(format!("{}.{}", file_stem(language), store.extension()))
+    }
+
+    pub fn open(
+        root: &Path,
+        config: &TranslatorConfig,
+        backend: &dyn TranslationBackend,
+        fingerprint: String,
+    ) -> Result<Self, TranslatorError> {
+        let path = Self::path_for(root, &config.cache_dir, &config.language, config.cache_store);
+        let lock = lock(&path)?;
+        let store = open_store(&path, config.cache_store, Some(root))?;
+        Ok(Self {
+            path,
+            store,
+            _lock: lock,
+            language: config.language.clone(),
+            backend: backend.name().to_string(),
+            model: backend.model().to_string(),
+            fingerprint,
+            invalidate_stale: config.invalidate_stale_cache,
+            stale: 0,
+        })
+    }
+
+    /// 查找缓存的译文。指纹与当前配置不同的条目会被计数，
+    /// 配置了 `invalidate-stale-cache` 时视为未命中
+    pub fn get(&mut self, key: &str) -> Result<Option<String>, TranslatorError> {
+        let Some(entry) = self.store.get(key)? else {
+            return Ok(None);
+        };
+        if entry.fingerprint.as_deref() != Some(self.fingerprint.as_str()) {
+            self.stale += 1;
+            if self.invalidate_stale {
+                return Ok(None);
+            }
+        }
+        self.store.record_hit(key)?;
+        Ok(Some(entry.translation))
+    }
+
+    pub fn insert(&mut self, key: &str, source: &str, translation: &str) -> Result<(), TranslatorError> {
+        let entry = NewEntry {
+            source,
+            translation,
+            language: &self.language,
+            backend: &self.backend,
+            model: &self.model,
+            fingerprint: &self.fingerprint,
+        };
+        self.store.put(key, &entry)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 本次构建中命中的、由其他后端/模型/提示生成的条目数
+    pub fn stale(&self) -> usize {
+        self.stale
+    }
+}
+
+/// 检查缓存文件本身能否读取，不会像打开 JSON 缓存那样把损坏的文件移走
+pub fn check_file(path: &Path, kind: StoreKind) -> Result<(), TranslatorError> {
+    match kind {
+        StoreKind::Json => json::check_file(path),
+        StoreKind::Sqlite => Ok(()),
+    }
+}
+
+/// 打开缓存。`legacy_root` 不为空时，新建的 JSON 缓存沿用该目录下旧版本的 `deepseek_cache.json`
+pub fn open_store(
+    path: &Path,
+    kind: StoreKind,
+    legacy_root: Option<&Path>,
+) -> Result<Box<dyn CacheStore>, TranslatorError> {
+    Ok(match kind {
+        StoreKind::Json => Box::new(JsonStore::open(path.to_path_buf(), legacy_root)?),
+        StoreKind::Sqlite => Box::new(SqliteStore::open(path)?),
+    })
+}
+
+/// 在缓存文件旁的 `<language>.lock` 上加排他锁，避免两个构建（如 `mdbook serve` 的重复构建）
+/// 同时读写同一个缓存。锁被占用时等待对方释放
+pub fn lock(path: &Path) -> Result<File, TranslatorError> {
+    if let Some(dir) = path.parent() {
+        create_dir(dir)?;
+    }
+    let lock_path = path.with_extension("lock");
+    let lock_error = |e: io::Error| TranslatorError::Cache(format!("failed to lock {}: {e}", lock_path.display()));
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .map_err(lock_error)?;
+
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) => {
+            eprintln!("Waiting for another build to release {}", lock_path.display());
+            file.lock().map_err(lock_error)?;
+        }
+        Err(TryLockError::Error(e)) => return Err(lock_error(e)),
+    }
+    Ok(file)
+}
+
+fn create_dir(dir: &Path) -> Result<(), TranslatorError> {
+    fs::create_dir_all(dir)
+        .map_err(|e| TranslatorError::Cache(format!("failed to create cache directory {}: {e}", dir.display())))
+}
+
+/// 语言名称转换成文件名，没有对应 ISO 代码的名称只保留字母和数字
+fn file_stem(language: &str) -> String {
+    iso_code(language).unwrap_or_else(|| {
+        language
+            .trim()
+            .to_lowercase()
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join("-")
+    })
+}
@@ -0,0 +1,330 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::{CacheRecord, CacheStore, NewEntry, StoredEntry, create_dir};
+use crate::error::TranslatorError;
+
+/// 旧版本写在当前目录下、所有语言共用的缓存文件
+const LEGACY_CACHE_FILE: &str = "deepseek_cache.json";
+
+/// 缓存文件格式的版本。旧版本的缓存是「key → 译文」的扁平对象，读取时自动升级
+const CACHE_VERSION: u64 = 2;
+
+/// 缓存文件的内容，`version` 在解析前单独检查
+#[derive(Deserialize)]
+struct CacheFile {
+    entries: BTreeMap<String, Entry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    translation: String,
+    /// 生成译文时的后端、模型、服务地址和提示的指纹，旧版本的缓存没有这一项
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fingerprint: Option<String>,
+}
+
+/// 整个缓存保存在一个 JSON 文件中，便于阅读和提交到仓库。
+/// 每次写入都会重写整个文件，大型书籍建议使用 SQLite
+pub struct JsonStore {
+    path: PathBuf,
+    entries: BTreeMap<String, Entry>,
+}
+
+impl JsonStore {
+    // 读取缓存，文件不存在时可以沿用 `legacy_root` 下旧版本的缓存
+    pub fn open(path: PathBuf, legacy_root: Option<&Path>) -> Result<Self, TranslatorError> {
+        let entries = if path.exists() {
+            match read_entries(&path) {
+                Ok(entries) => entries,
+                Err(TranslatorError::CacheCorrupt(reason)) => {
+                    // 解析失败的缓存里仍可能有大量已付费的译文，改名保留，不能被新缓存覆盖
+                    let backup = back_up(&path)?;
+                    eprintln!(
+                        "\x1b[33;1mWarning:\x1b[0m {reason}, moved it to {} and starting with an empty cache",
+                        backup.display()
+                    );
+                    BTreeMap::new()
+                }
+                Err(e) => return Err(e),
+            }
+        } else {
+            legacy_root.and_then(legacy_entries).unwrap_or_default()
+        };
+        Ok(Self { path, entries })
+    }
+}
+
+impl CacheStore for JsonStore {
+    fn get(&self, key: &str) -> Result<Option<StoredEntry>, TranslatorError> {
+        Ok(self.entries.get(key).map(|entry| StoredEntry {
+            translation: entry.translation.clone(),
+            fingerprint: entry.fingerprint.clone(),
+        }))
+    }
+
+    fn put(&mut self, key: &str, entry: &NewEntry) -> Result<(), TranslatorError> {
+        let entry = Entry {
+            translation: entry.translation.to_string(),
+            fingerprint: Some(entry.fingerprint.to_string()),
+        };
+        self.entries.insert(key.to_string(), entry);
+        self.save()
+    }
+
+    // 命中次数不写入 JSON 文件，避免每次构建都改动提交到仓库的缓存
+    fn record_hit(&mut self, _key: &str) -> Result<(), TranslatorError> {
+        Ok(())
+    }
+
+    fn records(&self) -> Result<Vec<CacheRecord>, TranslatorError> {
+        Ok(self.entries
+            .iter()
+            .map(|(key, entry)| CacheRecord {
+                key: key.clone(),
+                translation: entry.translation.clone(),
+                fingerprint: entry.fingerprint.clone(),
+                source: None,
+                language: None,
+                backend: None,
+                model: None,
+                hits: None,
+            })
+            .collect())
+    }
+
+    fn import(&mut self, records: Vec<CacheRecord>) -> Result<(), TranslatorError> {
+        for record in records {
+            let entry = Entry {
+                translation: record.translation,
+                fingerprint: record.fingerprint,
+            };
+            self.entries.insert(record.key, entry);
+        }
+        self.save()
+    }
+
+    fn remove(&mut self, keys: &[String]) -> Result<(), TranslatorError> {
+        for key in keys {
+            self.entries.remove(key);
+        }
+        self.save()
+    }
+}
+
+impl JsonStore {
+    // 写入缓存：先写临时文件再改名，中途被打断也不会留下写了一半的缓存
+    fn save(&self) -> Result<(), TranslatorError> {
+        if let Some(dir) = self.path.parent() {
+            create_dir(dir)?;
+        }
+        let file = json!({ "version": CACHE_VERSION, "entries": self.entries });
+        let data = serde_json::to_string_pretty(&file)
+            .map_err(|e| TranslatorError::Cache(format!("failed to serialize cache: {e}")))?;
+
+        let tmp_path = self.path.with_extension("json.tmp");
+        let write_error = |e: io::Error| TranslatorError::Cache(format!("failed to write {}: {e}", self.path.display()));
+        let mut tmp = File::create(&tmp_path).map_err(write_error)?;
+        tmp.write_all(data.as_bytes()).map_err(write_error)?;
+        tmp.sync_all().map_err(write_error)?;
+        fs::rename(&tmp_path, &self.path).map_err(write_error)
+    }
+}
+
+/// 检查缓存文件能否解析，不修改文件
+pub fn check_file(path: &Path) -> Result<(), TranslatorError> {
+    read_entries(path).map(|_| ())
+}
+
+/// 文件无法解析时返回 `CacheCorrupt`
+fn read_entries(path: &Path) -> Result<BTreeMap<String, Entry>, TranslatorError> {
+    let data = fs::read_to_string(path)
+        .map_err(|e| TranslatorError::Cache(format!("failed to read {}: {e}", path.display())))?;
+    let value = serde_json::from_str(&data).map_err(|e| ParseError::Invalid(e.to_string()));
+    match value.and_then(parse_entries) {
+        Ok(entries) => Ok(entries),
+        Err(ParseError::Invalid(reason)) => Err(TranslatorError::CacheCorrupt(format!(
+            "cache file {} is corrupt ({reason})",
+            path.display()
+        ))),
+        Err(ParseError::NewerVersion(version)) => Err(TranslatorError::Cache(format!(
+            "cache file {} version {version} was written by a newer mdbook-translator (supported: {CACHE_VERSION})",
+            path.display()
+        ))),
+    }
+}
+
+enum ParseError {
+    Invalid(String),
+    /// 更新版本的插件写入的缓存，不能当作损坏的文件移走
+    NewerVersion(u64),
+}
+
+impl From<String> for ParseError {
+    fn from(reason: String) -> Self {
+        ParseError::Invalid(reason)
+    }
+}
+
+/// 解析缓存文件，兼容旧版本「key → 译文」的扁平格式
+fn parse_entries(value: Value) -> Result<BTreeMap<String, Entry>, ParseError> {
+    match value.get("version").and_then(Value::as_u64) {
+        Some(CACHE_VERSION) => serde_json::from_value::<CacheFile>(value)
+            .map(|file| file.entries)
+            .map_err(|e| ParseError::Invalid(e.to_string())),
+        Some(version) => Err(ParseError::NewerVersion(version)),
+        None => {
+            let object = value.as_object().ok_or("expected a JSON object".to_string())?;
+            object
+                .iter()
+                .map(|(key, translation)| {
+                    let entry = Entry {
+                        translation: translation.as_str().ok_or(format!("entry {key} is not a string"))?.to_string(),
+                        fingerprint: None,
+                    };
+                    Ok((key.clone(), entry))
+                })
+                .collect::<Result<_, String>>()
+                .map_err(ParseError::Invalid)
+        }
+    }
+}
+
+/// 把无法解析的缓存文件改名为 `<name>.corrupt-<时间戳>`，返回新路径。
+/// 同一秒内已有备份时加上序号，不覆盖之前的备份
+fn back_up(path: &Path) -> Result<PathBuf, TranslatorError> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let backup = (0..)
+        .map(|n| {
+            let mut backup = path.as_os_str().to_owned();
+            match n {
+                0 => backup.push(format!(".corrupt-{timestamp}")),
+                n => backup.push(format!(".corrupt-{timestamp}-{n}")),
+            }
+            PathBuf::from(backup)
+        })
+        .find(|backup| !backup.exists())
+        .expect("unbounded range");
+    fs::rename(path, &backup)
+        .map_err(|e| TranslatorError::Cache(format!("failed to back up corrupt cache file {}: {e}", path.display())))?;
+    Ok(backup)
+}
+
+/// 新缓存文件不存在时，沿用书籍根目录下旧版本的缓存，避免已付费的译文作废。
+/// 旧缓存的 key 同样包含目标语言，其他语言的条目不会被命中
+fn legacy_entries(root: &Path) -> Option<BTreeMap<String, Entry>> {
+    let legacy = root.join(LEGACY_CACHE_FILE);
+    let data = fs::read_to_string(&legacy).ok()?;
+    let entries = serde_json::from_str(&data).ok().and_then(|value| parse_entries(value).ok())?;
+    eprintln!("Importing translations from legacy cache {}", legacy.display());
+    Some(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(translation: &str) -> NewEntry<'_> {
+        NewEntry {
+            source: "Hello",
+            translation,
+            language: "Chinese",
+            backend: "Mock",
+            model: "",
+            fingerprint: "fp",
+        }
+    }
+
+    fn files(dir: &Path) -> Vec<String> {
+        let mut names = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn saves_through_a_temporary_file() {
+        let root = tempfile::tempdir().unwrap();
+        let path = root.path().join("cache/zh.json");
+        let mut store = JsonStore::open(path.clone(), Some(root.path())).unwrap();
+        store.put("k", &entry("你好")).unwrap();
+
+        // 临时文件改名成缓存文件，不会留在目录中
+        assert_eq!(files(&root.path().join("cache")), ["zh.json"]);
+        let saved: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(
+            saved,
+            json!({ "version": CACHE_VERSION, "entries": { "k": { "translation": "你好", "fingerprint": "fp" } } })
+        );
+        let reopened = JsonStore::open(path, Some(root.path())).unwrap();
+        assert_eq!(reopened.get("k").unwrap().unwrap().translation, "你好");
+    }
+
+    #[test]
+    fn backs_up_a_corrupt_file() {
+        let root = tempfile::tempdir().unwrap();
+        let path = root.path().join("zh.json");
+        for content in ["{ not json", "[1, 2]"] {
+            fs::write(&path, content).unwrap();
+            let mut store = JsonStore::open(path.clone(), Some(root.path())).unwrap();
+            assert!(store.records().unwrap().is_empty());
+            store.put("k", &entry("你好")).unwrap();
+        }
+
+        // 同一秒内的两次备份不会互相覆盖
+        let names = files(root.path());
+        assert_eq!(names.len(), 3, "{names:?}");
+        assert_eq!(names[0], "zh.json");
+        assert!(names[1..].iter().all(|name| name.starts_with("zh.json.corrupt-")), "{names:?}");
+        let backups = names[1..]
+            .iter()
+            .map(|name| fs::read_to_string(root.path().join(name)).unwrap())
+            .collect::<Vec<_>>();
+        assert!(backups.contains(&"{ not json".to_string()) && backups.contains(&"[1, 2]".to_string()));
+    }
+
+    #[test]
+    fn rejects_a_newer_version() {
+        let root = tempfile::tempdir().unwrap();
+        let path = root.path().join("zh.json");
+        let content = r#"{"version": 99, "entries": {}}"#;
+        fs::write(&path, content).unwrap();
+
+        let error = JsonStore::open(path.clone(), Some(root.path())).err().unwrap();
+        assert!(matches!(&error, TranslatorError::Cache(message) if message.contains("version 99")), "{error}");
+        assert_eq!(fs::read_to_string(&path).unwrap(), content);
+        assert_eq!(files(root.path()), ["zh.json"]);
+    }
+
+    #[test]
+    fn imports_the_legacy_cache() {
+        let root = tempfile::tempdir().unwrap();
+        fs::write(root.path().join(LEGACY_CACHE_FILE), r#"{"k": "你好"}"#).unwrap();
+        let path = root.path().join(".translator-cache/zh.json");
+
+        let mut store = JsonStore::open(path.clone(), Some(root.path())).unwrap();
+        let legacy = store.get("k").unwrap().unwrap();
+        assert_eq!(legacy.translation, "你好");
+        assert_eq!(legacy.fingerprint, None);
+
+        // 写入后使用新的缓存文件，旧文件保持不变
+        store.put("other", &entry("世界")).unwrap();
+        let reopened = JsonStore::open(path, Some(root.path())).unwrap();
+        assert_eq!(reopened.records().unwrap().len(), 2);
+        assert_eq!(fs::read_to_string(root.path().join(LEGACY_CACHE_FILE)).unwrap(), r#"{"k": "你好"}"#);
+
+        // 维护命令新建的缓存不沿用旧缓存
+        let store = JsonStore::open(root.path().join("fr.json"), None).unwrap();
+        assert!(store.records().unwrap().is_empty());
+    }
+}
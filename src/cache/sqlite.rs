@@ -0,0 +1,276 @@
+use rusqlite::{Connection, ErrorCode, OptionalExtension, params};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::{CacheRecord, CacheStore, NewEntry, StoredEntry, create_dir};
+use crate::error::TranslatorError;
+
+/// 表结构的版本，记录在 `PRAGMA user_version` 中
+const SCHEMA_VERSION: i64 = 1;
+
+/// 每个分块一行的 SQLite 缓存，写入只涉及一行，适合大型书籍
+pub struct SqliteStore {
+    conn: Connection,
+}
+
+impl SqliteStore {
+    pub fn open(path: &Path) -> Result<Self, TranslatorError> {
+        if let Some(dir) = path.parent() {
+            create_dir(dir)?;
+        }
+        Self::open_connection(path).map_err(|e| match e {
+            TranslatorError::CacheCorrupt(reason) => {
+                TranslatorError::CacheCorrupt(format!("{}: {reason}", path.display()))
+            }
+            TranslatorError::Cache(reason) => TranslatorError::Cache(format!("{}: {reason}", path.display())),
+            e => e,
+        })
+    }
+
+    fn open_connection(path: &Path) -> Result<Self, TranslatorError> {
+        let conn = Connection::open(path)?;
+        // 另一个构建进程正在写入时等待，而不是立即报错
+        conn.busy_timeout(Duration::from_secs(30))?;
+
+        let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version > SCHEMA_VERSION {
+            return Err(TranslatorError::Cache(format!(
+                "cache database version {version} was written by a newer mdbook-translator (supported: {SCHEMA_VERSION})"
+            )));
+        }
+
+        conn.execute_batch(&format!(
+            "CREATE TABLE IF NOT EXISTS translations (
+                key          TEXT PRIMARY KEY,
+                source       TEXT NOT NULL,
+                translation  TEXT NOT NULL,
+                language     TEXT NOT NULL,
+                backend      TEXT NOT NULL,
+                model        TEXT NOT NULL,
+                fingerprint  TEXT NOT NULL,
+                created_at   INTEGER NOT NULL,
+                updated_at   INTEGER NOT NULL,
+                last_used_at INTEGER NOT NULL,
+                hits         INTEGER NOT NULL DEFAULT 0
+            );
+            PRAGMA user_version = {SCHEMA_VERSION};"
+        ))?;
+
+        Ok(Self { conn })
+    }
+}
+
+/// 数据库文件损坏或不是 SQLite 文件时为 `CacheCorrupt`，其余为 `Cache`
+impl From<rusqlite::Error> for TranslatorError {
+    fn from(e: rusqlite::Error) -> Self {
+        match e.sqlite_error_code() {
+            Some(ErrorCode::DatabaseCorrupt | ErrorCode::NotADatabase) => TranslatorError::CacheCorrupt(e.to_string()),
+            _ => TranslatorError::Cache(e.to_string()),
+        }
+    }
+}
+
+impl CacheStore for SqliteStore {
+    fn get(&self, key: &str) -> Result<Option<StoredEntry>, TranslatorError> {
+        let entry = self
+            .conn
+            .query_row(
+                "SELECT translation, fingerprint FROM translations WHERE key = ?1",
+                params![key],
+                |row| {
+                    Ok(StoredEntry {
+                        translation: row.get(0)?,
+                        fingerprint: row.get(1)?,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(entry)
+    }
+
+    fn put(&mut self, key: &str, entry: &NewEntry) -> Result<(), TranslatorError> {
+        let now = unix_time();
+        self.conn.execute(
+            "INSERT INTO translations
+                (key, source, translation, language, backend, model, fingerprint, created_at, updated_at, last_used_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?8, ?8)
+             ON CONFLICT(key) DO UPDATE SET
+                source = excluded.source,
+                translation = excluded.translation,
+                language = excluded.language,
+                backend = excluded.backend,
+                model = excluded.model,
+                fingerprint = excluded.fingerprint,
+                updated_at = excluded.updated_at,
+                last_used_at = excluded.last_used_at",
+            params![
+                key,
+                entry.source,
+                entry.translation,
+                entry.language,
+                entry.backend,
+                entry.model,
+                entry.fingerprint,
+                now
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn record_hit(&mut self, key: &str) -> Result<(), TranslatorError> {
+        self.conn.execute(
+            "UPDATE translations SET hits = hits + 1, last_used_at = ?2 WHERE key = ?1",
+            params![key, unix_time()],
+        )?;
+        Ok(())
+    }
+
+    fn records(&self) -> Result<Vec<CacheRecord>, TranslatorError> {
+        let mut statement = self.conn.prepare(
+            "SELECT key, translation, fingerprint, source, language, backend, model, hits
+             FROM translations ORDER BY key",
+        )?;
+        let rows = statement.query_map([], |row| {
+            Ok(CacheRecord {
+                key: row.get(0)?,
+                translation: row.get(1)?,
+                fingerprint: row.get(2)?,
+                source: row.get(3)?,
+                language: row.get(4)?,
+                backend: row.get(5)?,
+                model: row.get(6)?,
+                hits: Some(row.get::<_, i64>(7)? as u64),
+            })
+        })?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    fn import(&mut self, records: Vec<CacheRecord>) -> Result<(), TranslatorError> {
+        let now = unix_time();
+        let transaction = self.conn.transaction()?;
+        {
+            // JSON 缓存导出的记录没有原文、语言等信息，缺少的字段保留已有行中的值，
+            // 新行则存为空字符串。后端、模型和指纹描述的是译文本身，只在译文不变时保留
+            let mut statement = transaction.prepare(
+                "INSERT INTO translations
+                    (key, source, translation, language, backend, model, fingerprint, created_at, updated_at, last_used_at, hits)
+                 VALUES (?1, COALESCE(?2, ''), ?3, COALESCE(?4, ''), COALESCE(?5, ''), COALESCE(?6, ''), COALESCE(?7, ''),
+                         ?8, ?8, ?8, COALESCE(?9, 0))
+                 ON CONFLICT(key) DO UPDATE SET
+                    source = COALESCE(?2, source),
+                    translation = excluded.translation,
+                    language = COALESCE(?4, language),
+                    backend = CASE WHEN translation = excluded.translation THEN COALESCE(?5, backend) ELSE excluded.backend END,
+                    model = CASE WHEN translation = excluded.translation THEN COALESCE(?6, model) ELSE excluded.model END,
+                    fingerprint = CASE WHEN translation = excluded.translation
+                        THEN COALESCE(?7, fingerprint) ELSE excluded.fingerprint END,
+                    updated_at = excluded.updated_at,
+                    hits = COALESCE(?9, hits)",
+            )?;
+            let present = |value: Option<String>| value.filter(|v| !v.is_empty());
+            for record in records {
+                statement.execute(params![
+                    record.key,
+                    present(record.source),
+                    record.translation,
+                    present(record.language),
+                    present(record.backend),
+                    present(record.model),
+                    present(record.fingerprint),
+                    now,
+                    record.hits.map(|hits| hits as i64)
+                ])?;
+            }
+        }
+        Ok(transaction.commit()?)
+    }
+
+    fn remove(&mut self, keys: &[String]) -> Result<(), TranslatorError> {
+        let transaction = self.conn.transaction()?;
+        {
+            let mut statement = transaction.prepare("DELETE FROM translations WHERE key = ?1")?;
+            for key in keys {
+                statement.execute(params![key])?;
+            }
+        }
+        Ok(transaction.commit()?)
+    }
+
+    fn check(&self) -> Vec<String> {
+        let messages = self.conn.prepare("PRAGMA integrity_check").and_then(|mut statement| {
+            statement
+                .query_map([], |row| row.get::<_, String>(0))?
+                .collect::<rusqlite::Result<Vec<_>>>()
+        });
+        messages
+            .unwrap_or_else(|e| vec![e.to_string()])
+            .into_iter()
+            .filter(|message| message != "ok")
+            .collect()
+    }
+}
+
+fn unix_time() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{Value, json};
+
+    fn records(store: &SqliteStore) -> Value {
+        serde_json::to_value(store.records().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn import_keeps_metadata_that_records_lack() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = SqliteStore::open(&dir.path().join("zh.sqlite")).unwrap();
+        for (key, translation) in [("a", "你好"), ("b", "世界")] {
+            let entry = NewEntry {
+                source: "Hello",
+                translation,
+                language: "Chinese",
+                backend: "DeepSeek",
+                model: "deepseek-chat",
+                fingerprint: "fp",
+            };
+            store.put(key, &entry).unwrap();
+        }
+        store.record_hit("a").unwrap();
+        store.record_hit("a").unwrap();
+
+        // JSON 缓存导出的记录只有 key、译文和指纹
+        let imported = json!([
+            { "key": "a", "translation": "你好" },
+            { "key": "b", "translation": "世界！", "fingerprint": "fp2", "model": "" },
+            { "key": "c", "translation": "新的", "hits": 5 },
+        ]);
+        store.import(serde_json::from_value(imported).unwrap()).unwrap();
+
+        assert_eq!(
+            records(&store),
+            json!([
+                // 译文不变：保留全部元数据和命中次数
+                {
+                    "key": "a", "translation": "你好", "fingerprint": "fp", "source": "Hello", "language": "Chinese",
+                    "backend": "DeepSeek", "model": "deepseek-chat", "hits": 2,
+                },
+                // 译文改变：原文和语言保留，描述旧译文的后端、模型和指纹不再保留
+                {
+                    "key": "b", "translation": "世界！", "fingerprint": "fp2", "source": "Hello", "language": "Chinese",
+                    "backend": "", "model": "", "hits": 0,
+                },
+                // 新行：缺少的字段存为空字符串
+                {
+                    "key": "c", "translation": "新的", "fingerprint": "", "source": "", "language": "",
+                    "backend": "", "model": "", "hits": 5,
+                },
+            ])
+        );
+    }
+}
@@ -0,0 +1,259 @@
+use anyhow::{Context, Result, anyhow};
+use clap::ArgMatches;
+use mdbook::MDBook;
+use mdbook::book::Book;
+use mdbook::preprocess::{LinkPreprocessor, Preprocessor, PreprocessorContext};
+use serde_json::json;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::cache::{self, Cache, CacheRecord, CacheStore, StoreKind};
+use crate::config::TranslatorConfig;
+use crate::error::TranslatorError;
+use crate::protect::{placeholder_count, verify_placeholders};
+use crate::translate_preprocessor::DeepSeekTranslator;
+
+/// `mdbook-translator cache <command>`：在 mdBook 之外查看和维护翻译缓存
+pub fn handle_cache(pre: &mut DeepSeekTranslator, sub_args: &ArgMatches) -> Result<()> {
+    let (command, args) = sub_args
+        .subcommand()
+        .ok_or_else(|| anyhow!("missing cache command"))?;
+
+    let dir = args.get_one::<String>("dir").map(String::as_str).unwrap_or(".");
+    let md = MDBook::load(dir).with_context(|| format!("unable to load the book in {dir}"))?;
+    let mut config = TranslatorConfig::from_table(md.config.get_preprocessor(pre.name()))?;
+    if let Some(language) = args.get_one::<String>("language") {
+        config.language = language.clone();
+    }
+    pre.set_config(config)?;
+
+    match command {
+        "stats" => stats(pre, &md.root),
+        "prune" => prune(pre, &md, args.get_flag("dry-run")),
+        "export" => export(pre, &md.root, args.get_one::<String>("output")),
+        "import" => import(pre, &md.root, args.get_one::<String>("file").expect("Required argument")),
+        "verify" => verify(pre, &md.root),
+        other => Err(anyhow!("unknown cache command {other:?}")),
+    }
+}
+
+/// 当前配置的目标语言对应的缓存文件
+fn cache_path(pre: &DeepSeekTranslator, root: &Path) -> PathBuf {
+    let config = &pre.config;
+    Cache::path_for(root, &config.cache_dir, &config.language, config.cache_store)
+}
+
+fn existing_cache_path(pre: &DeepSeekTranslator, root: &Path) -> Result<PathBuf> {
+    let path = cache_path(pre, root);
+    if !path.exists() {
+        return Err(anyhow!("no cache for {:?} at {}", pre.config.language, path.display()));
+    }
+    Ok(path)
+}
+
+/// 缓存目录中每种语言的条目数、文件大小和命中情况
+fn stats(pre: &DeepSeekTranslator, root: &Path) -> Result<()> {
+    let dir = root.join(&pre.config.cache_dir);
+    let mut files = match fs::read_dir(&dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter_map(|path| Some((StoreKind::from_path(&path)?, path)))
+            .collect::<Vec<_>>(),
+        Err(_) => Vec::new(),
+    };
+    files.sort_by(|a, b| a.1.cmp(&b.1));
+
+    println!("Cache directory: {}", dir.display());
+    if files.is_empty() {
+        println!("No cached translations");
+        return Ok(());
+    }
+
+    println!("{:<12} {:<8} {:>8} {:>12} {:>10} {:>10}", "language", "store", "entries", "size", "hits", "hit rate");
+    let mut has_json = false;
+    for (kind, path) in files {
+        let _lock = cache::lock(&path)?;
+        // 一个文件损坏时照常列出其他语言
+        let records = match open_existing(&path, kind).and_then(|store| store.records()) {
+            Ok(records) => records,
+            Err(e) => {
+                eprintln!("\x1b[33;1mWarning:\x1b[0m {e}");
+                continue;
+            }
+        };
+        has_json |= kind == StoreKind::Json;
+        let size = fs::metadata(&path).map(|m| m.len()).unwrap_or_default();
+        let language = path.file_stem().unwrap_or_default().to_string_lossy();
+
+        // 只有 SQLite 记录命中次数；命中率为至少命中过一次的条目所占的比例
+        let (hits, rate) = match kind {
+            StoreKind::Sqlite if !records.is_empty() => {
+                let hits = records.iter().filter_map(|r| r.hits).sum::<u64>();
+                let used = records.iter().filter(|r| r.hits.unwrap_or_default() > 0).count();
+                (hits.to_string(), format!("{:.1}%", used as f64 * 100.0 / records.len() as f64))
+            }
+            _ => ("-".to_string(), "-".to_string()),
+        };
+        println!(
+            "{:<12} {:<8} {:>8} {:>12} {:>10} {:>10}",
+            language,
+            kind.extension(),
+            records.len(),
+            format_size(size),
+            hits,
+            rate
+        );
+    }
+    if has_json {
+        println!("JSON caches do not record hits; use cache-store = \"sqlite\" to track them");
+    }
+    Ok(())
+}
+
+/// 删除当前书籍不再用到的条目
+fn prune(pre: &DeepSeekTranslator, md: &MDBook, dry_run: bool) -> Result<()> {
+    let path = existing_cache_path(pre, &md.root)?;
+    let book = expanded_book(pre, md)?;
+    let used = pre.cache_keys(&book);
+
+    let _lock = cache::lock(&path)?;
+    let mut store = open_existing(&path, pre.config.cache_store)?;
+    let records = store.records()?;
+    let unused = records
+        .iter()
+        .filter(|record| !used.contains(&record.key))
+        .map(|record| record.key.clone())
+        .collect::<Vec<_>>();
+
+    if dry_run {
+        for record in records.iter().filter(|r| !used.contains(&r.key)) {
+            println!("{}  {:?}", record.key, preview(&record.translation));
+        }
+        println!("Would remove {} of {} entries from {}", unused.len(), records.len(), path.display());
+    } else {
+        store.remove(&unused)?;
+        println!("Removed {} of {} entries from {}", unused.len(), records.len(), path.display());
+    }
+    Ok(())
+}
+
+/// 与构建时一样，本插件在 links 之后运行时先展开 `{{#include}}` 等指令，否则算出的 key 对不上。
+/// 其他预处理器不会运行，它们改动过的章节可能被误判为不再使用，可以先用 `--dry-run` 确认
+fn expanded_book(pre: &DeepSeekTranslator, md: &MDBook) -> Result<Book> {
+    if pre.config.before.iter().any(|name| name == "links") {
+        return Ok(md.book.clone());
+    }
+    let ctx: PreprocessorContext = serde_json::from_value(json!({
+        "root": md.root,
+        "config": md.config,
+        "renderer": "html",
+        "mdbook_version": mdbook::MDBOOK_VERSION,
+    }))?;
+    LinkPreprocessor::new().run(&ctx, md.book.clone())
+}
+
+/// 以 JSON Lines 格式导出当前语言的缓存，每行一条
+fn export(pre: &DeepSeekTranslator, root: &Path, output: Option<&String>) -> Result<()> {
+    let path = existing_cache_path(pre, root)?;
+    let _lock = cache::lock(&path)?;
+    let records = open_existing(&path, pre.config.cache_store)?.records()?;
+
+    let mut writer: Box<dyn Write> = match output.map(String::as_str) {
+        Some("-") | None => Box::new(BufWriter::new(io::stdout().lock())),
+        Some(file) => Box::new(BufWriter::new(
+            File::create(file).with_context(|| format!("unable to create {file}"))?,
+        )),
+    };
+    for record in &records {
+        serde_json::to_writer(&mut writer, record)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()?;
+
+    eprintln!("Exported {} entries from {}", records.len(), path.display());
+    Ok(())
+}
+
+/// 导入 `export` 生成的 JSON Lines，已有的 key 会被覆盖
+fn import(pre: &DeepSeekTranslator, root: &Path, file: &str) -> Result<()> {
+    let reader: Box<dyn BufRead> = match file {
+        "-" => Box::new(io::stdin().lock()),
+        file => Box::new(BufReader::new(File::open(file).with_context(|| format!("unable to open {file}"))?)),
+    };
+
+    let mut records = Vec::new();
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: CacheRecord = serde_json::from_str(&line)
+            .with_context(|| format!("{file}:{}: invalid cache record", number + 1))?;
+        records.push(record);
+    }
+
+    let path = cache_path(pre, root);
+    let _lock = cache::lock(&path)?;
+    let count = records.len();
+    // 已有的缓存无法解析时报错，不移走文件；新建的缓存不导入旧版本的 `deepseek_cache.json`
+    let mut store = if path.exists() {
+        open_existing(&path, pre.config.cache_store)?
+    } else {
+        cache::open_store(&path, pre.config.cache_store, None)?
+    };
+    store.import(records)?;
+
+    println!("Imported {count} entries into {}", path.display());
+    Ok(())
+}
+
+/// 检查缓存文件能否读取，以及每条译文是否为空、占位符是否与原文一致
+fn verify(pre: &DeepSeekTranslator, root: &Path) -> Result<()> {
+    let path = existing_cache_path(pre, root)?;
+    let _lock = cache::lock(&path)?;
+    let store = open_existing(&path, pre.config.cache_store)?;
+    let mut problems = store.check();
+    let records = store.records()?;
+    for record in &records {
+        if record.translation.trim().is_empty() {
+            problems.push(format!("{}: empty translation", record.key));
+        }
+        // JSON 缓存不保存原文，只能检查译文中的占位符是否从 `@@MDT0@@` 起连续且各出现一次
+        let count = match record.source.as_deref().filter(|s| !s.is_empty()) {
+            Some(source) => placeholder_count(source),
+            None => placeholder_count(&record.translation),
+        };
+        if let Err(reason) = verify_placeholders(&record.translation, count) {
+            problems.push(format!("{}: {reason}", record.key));
+        }
+    }
+
+    for problem in &problems {
+        println!("{problem}");
+    }
+    if problems.is_empty() {
+        println!("{}: {} entries OK", path.display(), records.len());
+        Ok(())
+    } else {
+        Err(anyhow!("found {} problems in {}", problems.len(), path.display()))
+    }
+}
+
+/// 打开已有的缓存。与构建不同，维护命令遇到损坏的 JSON 文件时直接报错，不把它改名备份
+fn open_existing(path: &Path, kind: StoreKind) -> Result<Box<dyn CacheStore>, TranslatorError> {
+    cache::check_file(path, kind)?;
+    cache::open_store(path, kind, None)
+}
+
+fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..1024 => format!("{bytes} B"),
+        1024..1_048_576 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+fn preview(text: &str) -> String {
+    text.chars().take(60).collect()
+}
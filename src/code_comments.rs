@@ -0,0 +1,131 @@
+use std::ops::Range;
+
+/// 各语言行注释的起始标记
+fn line_comment_marker(lang: &str) -> Option<&'static str> {
+    match lang.to_ascii_lowercase().as_str() {
+        "rust" | "rs" | "c" | "cpp" | "c++" | "h" | "hpp" | "java" | "javascript" | "js" | "jsx"
+        | "typescript" | "ts" | "tsx" | "go" | "golang" | "swift" | "kotlin" | "kt" | "csharp"
+        | "cs" | "c#" | "scala" | "dart" | "zig" | "solidity" | "proto" | "protobuf" => Some("//"),
+        "python" | "py" | "sh" | "bash" | "shell" | "zsh" | "fish" | "ruby" | "rb"
+        | "toml" | "yaml" | "yml" | "perl" | "pl" | "r" | "makefile" | "make" | "dockerfile"
+        | "cmake" | "elixir" | "ex" | "nix" | "powershell" | "ps1" => Some("#"),
+        "sql" | "lua" | "haskell" | "hs" | "elm" | "ada" => Some("--"),
+        _ => None,
+    }
+}
+
+/// 找出代码块中每条行注释的文字部分（不含注释标记）的字节范围，
+/// 不支持的语言返回空列表
+pub fn comment_ranges(block: &str, lang: &str) -> Vec<Range<usize>> {
+    let Some(marker) = line_comment_marker(lang) else {
+        return Vec::new();
+    };
+    // Rust 等语言里的单引号是字符字面量或生命周期，不当作字符串处理
+    let quotes: &[char] = if marker == "//" { &['"'] } else { &['"', '\''] };
+
+    let mut ranges = Vec::new();
+    let mut offset = 0;
+    let line_count = block.split_inclusive('\n').count();
+    for (index, line) in block.split_inclusive('\n').enumerate() {
+        let line_start = offset;
+        offset += line.len();
+
+        // 跳过开头和结尾的围栏行
+        let trimmed = line.trim_start();
+        let is_fence = trimmed.starts_with("```") || trimmed.starts_with("~~~");
+        if is_fence && (index == 0 || index + 1 == line_count) {
+            continue;
+        }
+
+        let Some(start) = find_marker(line, marker, quotes) else {
+            continue;
+        };
+        let after_marker = &line[start + marker.len()..];
+        // shebang 不是注释
+        if marker == "#" && after_marker.starts_with('!') && line[..start].trim().is_empty() {
+            continue;
+        }
+        // `///`、`//!`、`##` 等文档注释的额外标记字符也保留在原文中
+        let extra = after_marker.len()
+            - after_marker
+                .trim_start_matches(|c: char| c == '!' || marker.starts_with(c))
+                .len();
+        let rest = &after_marker[extra..];
+        let content_start = start + marker.len() + extra + (rest.len() - rest.trim_start().len());
+        let content = line[content_start..].trim_end();
+        if content.chars().any(char::is_alphabetic) {
+            let content_start = line_start + content_start;
+            ranges.push(content_start..content_start + content.len());
+        }
+    }
+    ranges
+}
+
+/// 在一行中查找不在字符串字面量里的注释标记。`#` 和 `--` 常出现在代码中间
+/// （如 shell 的 `${#arr[@]}`、YAML 中的 `http://x#frag`），只在行首或空白之后才算注释
+fn find_marker(line: &str, marker: &str, quotes: &[char]) -> Option<usize> {
+    let needs_space = marker != "//";
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        if let Some(q) = quote {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == q {
+                quote = None;
+            }
+        } else if quotes.contains(&c) {
+            quote = Some(c);
+        } else if line[i..].starts_with(marker)
+            && (!needs_space || line[..i].chars().next_back().is_none_or(char::is_whitespace))
+        {
+            return Some(i);
+        }
+    }
+    None
+}
+
+/// 用译文替换代码块中的注释，`translations` 与 `ranges` 一一对应
+pub fn replace_comments(block: &str, ranges: &[Range<usize>], translations: &[&str]) -> String {
+    let mut output = String::with_capacity(block.len());
+    let mut last = 0;
+    for (range, translated) in ranges.iter().zip(translations) {
+        output.push_str(&block[last..range.start]);
+        output.push_str(translated.trim());
+        last = range.end;
+    }
+    output.push_str(&block[last..]);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comments<'a>(block: &'a str, lang: &str) -> Vec<&'a str> {
+        comment_ranges(block, lang).into_iter().map(|range| &block[range]).collect()
+    }
+
+    #[test]
+    fn hash_comments_need_leading_whitespace() {
+        let block = "```bash\n#!/bin/sh\n# count the items\necho ${#arr[@]} # print it\necho \"# not a comment\"\n```\n";
+        assert_eq!(comments(block, "bash"), ["count the items", "print it"]);
+        assert!(comments("```yaml\nurl: http://example.com/#frag\n```\n", "yaml").is_empty());
+        assert!(comments("```console\n# apt install foo\n```\n", "console").is_empty());
+    }
+
+    #[test]
+    fn slash_comments_and_strings() {
+        let block = "```rust\n/// Doc comment.\nlet url = \"https://example.com\"; // trailing note\nx=1;// tight\n```\n";
+        assert_eq!(comments(block, "rust"), ["Doc comment.", "trailing note", "tight"]);
+    }
+
+    #[test]
+    fn replaces_comments_in_place() {
+        let block = "```python\nx = 1  # set x\n# done\n```\n";
+        let ranges = comment_ranges(block, "python");
+        assert_eq!(replace_comments(block, &ranges, &["设置 x", "完成"]), "```python\nx = 1  # 设置 x\n# 完成\n```\n");
+    }
+}
@@ -1,11 +1,11 @@
 use mdbook::preprocess::{Preprocessor, CmdPreprocessor};
-use clap::{Arg, ArgMatches, Command};
+use clap::{Arg, ArgAction, ArgMatches, Command};
 use semver::{Version, VersionReq};
 use std::io;
 use mdbook::errors::Error;
 use anyhow::Result;
 use std::process;
-use toml::value::Value;
+use crate::config::TranslatorConfig;
 use crate::translate_preprocessor::DeepSeekTranslator;
 
 pub fn handle_preprocessing(pre: &mut DeepSeekTranslator) -> Result<(), Error> {
@@ -24,39 +24,11 @@ pub fn handle_preprocessing(pre: &mut DeepSeekTranslator) -> Result<(), Error> {
         );
     }
 
-    let language = 
-        ctx.config.get("preprocessor")
-            .and_then(|p| p.get("translator"))
-            .and_then(|t| t.get("language"));
-    let ext_prompt = 
-        ctx.config.get("preprocessor")
-            .and_then(|p| p.get("translator"))
-            .and_then(|t| t.get("prompt"));
-    let proxy = 
-        ctx.config.get("preprocessor")
-            .and_then(|p| p.get("translator"))
-            .and_then(|t| t.get("proxy"));
+    let config = TranslatorConfig::from_table(ctx.config.get_preprocessor(pre.name()))?;
+    pre.set_config(config)?;
 
-    if let Some(Value::String(language_config)) = language {
-        if !language_config.is_empty() {
-            pre.set_language(language_config);
-        }
-    }
-
-    if let Some(Value::String(prompt_config)) = ext_prompt {
-        if !prompt_config.is_empty() {
-            pre.set_prompt(prompt_config);
-        }
-    }
-
-    if let Some(Value::String(proxy_config)) = proxy {
-        if !proxy_config.is_empty() {
-            pre.set_proxy(proxy_config);
-        }
-    }
-
-    eprintln!("target_lang: {:?}", pre.target_lang);
-    eprintln!("prompt: {:?}", pre.prompt);
+    eprintln!("target_lang: {:?}", pre.config.language);
+    eprintln!("prompt: {:?}", pre.config.prompt);
 
     let processed_book = pre.run(&ctx, book)?;
     serde_json::to_writer(io::stdout(), &processed_book)?;
@@ -86,4 +58,45 @@ pub fn make_app() -> Command {
                 .arg(Arg::new("renderer").required(true))
                 .about("Check whether a renderer is supported by this preprocessor"),
         )
+        .subcommand(
+            Command::new("cache")
+                .about("Inspect and maintain the translation cache")
+                .subcommand_required(true)
+                .arg(
+                    Arg::new("dir")
+                        .long("dir")
+                        .short('d')
+                        .global(true)
+                        .help("Root directory of the book (the one containing book.toml), defaults to the current directory"),
+                )
+                .arg(
+                    Arg::new("language")
+                        .long("language")
+                        .short('l')
+                        .global(true)
+                        .help("Target language of the cache, defaults to `language` in book.toml"),
+                )
+                .subcommand(Command::new("stats").about("Show entries, size and hits of every cached language"))
+                .subcommand(
+                    Command::new("prune")
+                        .about("Remove entries that the current book no longer uses")
+                        .arg(
+                            Arg::new("dry-run")
+                                .long("dry-run")
+                                .action(ArgAction::SetTrue)
+                                .help("List the entries that would be removed without removing them"),
+                        ),
+                )
+                .subcommand(
+                    Command::new("export")
+                        .about("Export the cache as JSON Lines")
+                        .arg(Arg::new("output").long("output").short('o').help("Output file, defaults to stdout")),
+                )
+                .subcommand(
+                    Command::new("import")
+                        .about("Import JSON Lines written by `cache export`")
+                        .arg(Arg::new("file").required(true).help("Input file, `-` reads stdin")),
+                )
+                .subcommand(Command::new("verify").about("Check the cache for unreadable files, empty translations and broken placeholders")),
+        )
 }
\ No newline at end of file
@@ -0,0 +1,209 @@
+use anyhow::{Result, anyhow};
+use serde::Deserialize;
+use toml::value::{Table, Value};
+
+use crate::anchors::AnchorStyle;
+use crate::backend::{OllamaEndpoint, TagHandling};
+use crate::cache::StoreKind;
+
+/// 可选的翻译后端，对应 `backend = "..."`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    DeepSeek,
+    OpenAi,
+    Ollama,
+    Anthropic,
+    DeepL,
+    LibreTranslate,
+    Command,
+    Mock,
+}
+
+/// 分块翻译失败时的处理方式，对应 `on-error = "..."`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OnError {
+    /// 中止构建
+    Fail,
+    /// 保留原文
+    Fallback,
+    /// 保留原文，并在前面加上「未翻译」的提示
+    FallbackAnnotated,
+}
+
+/// `[preprocessor.translator]` 中的全部配置。字符串为空等同于未设置。
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", default)]
+pub struct TranslatorConfig {
+    // 以下几项由 mdBook 自己读取，这里只是为了不把它们当成未知的配置
+    pub command: Option<String>,
+    pub renderers: Vec<String>,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+
+    /// 目标语言，如 "Chinese"
+    pub language: String,
+    /// 源语言，为空时自动识别
+    pub source_language: String,
+    /// 附加给翻译服务的提示
+    pub prompt: String,
+    /// HTTP 代理
+    pub proxy: String,
+    /// 暂时性错误的最大重试次数
+    pub max_retries: u32,
+    /// 第一次重试前等待的秒数，之后每次翻倍
+    pub retry_base_delay: f64,
+    /// 两次重试之间最多等待的秒数
+    pub retry_max_delay: f64,
+    /// 分块翻译失败时的处理方式
+    pub on_error: OnError,
+    /// 同时发出的翻译请求数
+    pub concurrency: usize,
+    /// 每分钟最多发出的请求数
+    pub max_requests_per_minute: Option<u64>,
+    /// 每分钟最多消耗的 token 数（估算值）
+    pub max_tokens_per_minute: Option<u64>,
+    /// 缓存目录，相对路径相对于书籍根目录
+    pub cache_dir: String,
+    /// 重新翻译由其他后端、模型、服务地址或提示生成的缓存条目
+    pub invalidate_stale_cache: bool,
+    /// 缓存的存储方式
+    pub cache_store: StoreKind,
+
+    pub backend: BackendKind,
+    pub base_url: Option<String>,
+    pub model: Option<String>,
+    /// 保存 API 密钥的环境变量名
+    pub api_key_env: Option<String>,
+    /// Ollama 使用的接口
+    pub endpoint: OllamaEndpoint,
+    /// Anthropic 的最大输出 token 数
+    pub max_tokens: u64,
+    /// DeepL 的标记处理方式
+    pub tag_handling: Option<TagHandling>,
+    pub ignore_tags: Vec<String>,
+    pub glossary_id: Option<String>,
+    /// `backend = "command"` 时运行的程序
+    pub backend_command: Option<String>,
+
+    /// 只翻译注释的代码块语言
+    pub translate_code_comments: Vec<String>,
+    pub stable_anchors: Option<AnchorStyle>,
+    pub translate_titles: bool,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl Default for TranslatorConfig {
+    fn default() -> Self {
+        Self {
+            command: None,
+            renderers: Vec::new(),
+            before: Vec::new(),
+            after: Vec::new(),
+            language: String::new(),
+            source_language: String::new(),
+            prompt: String::new(),
+            proxy: String::new(),
+            max_retries: 5,
+            retry_base_delay: 1.0,
+            retry_max_delay: 60.0,
+            on_error: OnError::Fail,
+            concurrency: 4,
+            max_requests_per_minute: None,
+            max_tokens_per_minute: None,
+            cache_dir: ".translator-cache".to_string(),
+            invalidate_stale_cache: false,
+            cache_store: StoreKind::Json,
+            backend: BackendKind::DeepSeek,
+            base_url: None,
+            model: None,
+            api_key_env: None,
+            endpoint: OllamaEndpoint::Chat,
+            max_tokens: 8192,
+            tag_handling: None,
+            ignore_tags: Vec::new(),
+            glossary_id: None,
+            backend_command: None,
+            translate_code_comments: Vec::new(),
+            stable_anchors: None,
+            translate_titles: true,
+            include: Vec::new(),
+            exclude: Vec::new(),
+        }
+    }
+}
+
+impl TranslatorConfig {
+    /// 从 book.toml 的 `[preprocessor.translator]` 表读取配置。
+    /// 未知的配置项只打印警告，类型错误会指出出错的配置项。
+    pub fn from_table(table: Option<&Table>) -> Result<Self> {
+        let value = Value::Table(table.cloned().unwrap_or_default());
+
+        let mut unknown = Vec::new();
+        let mut record = |path: serde_ignored::Path| unknown.push(path.to_string());
+        let deserializer = serde_ignored::Deserializer::new(value, &mut record);
+        let config: TranslatorConfig = serde_path_to_error::deserialize(deserializer)
+            .map_err(|e| anyhow!("invalid preprocessor.translator.{}: {}", e.path(), e.inner()))?;
+
+        for key in unknown {
+            eprintln!("\x1b[33;1mWarning:\x1b[0m unknown key preprocessor.translator.{key} is ignored");
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.language.trim().is_empty() {
+            return Err(anyhow!("preprocessor.translator.language must be set to the target language"));
+        }
+        if self.cache_dir.trim().is_empty() {
+            return Err(anyhow!("preprocessor.translator.cache-dir must not be empty"));
+        }
+        if self.concurrency == 0 {
+            return Err(anyhow!("preprocessor.translator.concurrency must be a positive integer"));
+        }
+        for (key, limit) in [
+            ("max-requests-per-minute", self.max_requests_per_minute),
+            ("max-tokens-per-minute", self.max_tokens_per_minute),
+        ] {
+            if limit == Some(0) {
+                return Err(anyhow!("preprocessor.translator.{key} must be a positive integer"));
+            }
+        }
+        if self.max_tokens == 0 {
+            return Err(anyhow!("preprocessor.translator.max-tokens must be a positive integer"));
+        }
+        for (key, seconds) in [("retry-base-delay", self.retry_base_delay), ("retry-max-delay", self.retry_max_delay)] {
+            // 超出 Duration 范围的值会让 Duration::from_secs_f64 panic
+            if !(0.0..=86400.0).contains(&seconds) {
+                return Err(anyhow!("preprocessor.translator.{key} must be between 0 and 86400 seconds"));
+            }
+        }
+        if self.before.iter().any(|n| n == "links") && self.after.iter().any(|n| n == "links") {
+            return Err(anyhow!(
+                "preprocessor.translator cannot list \"links\" in both `before` and `after`"
+            ));
+        }
+        Ok(())
+    }
+
+    /// 去掉空字符串后的配置值
+    pub fn base_url(&self) -> Option<&str> {
+        non_empty(&self.base_url)
+    }
+
+    pub fn model(&self) -> Option<&str> {
+        non_empty(&self.model)
+    }
+
+    pub fn api_key_env(&self) -> Option<&str> {
+        non_empty(&self.api_key_env)
+    }
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().filter(|s| !s.is_empty())
+}
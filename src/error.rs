@@ -0,0 +1,73 @@
+use std::error::Error;
+use std::fmt;
+
+/// 翻译过程中的错误。`run` 把它交给 mdBook，由 mdBook 打印后中止构建
+#[derive(Debug, Clone)]
+pub enum TranslatorError {
+    /// 没有设置 API 密钥，或密钥被服务端拒绝（401/403）
+    Auth(String),
+    /// 重试用尽后仍被限流（429）
+    RateLimited(String),
+    /// 服务端返回了错误或无法解析的响应
+    BadResponse(String),
+    /// 超时、连接失败等网络错误
+    Network(String),
+    /// 其他后端错误，如外部命令以非零状态退出
+    Backend(String),
+    /// 多次请求后译文仍未通过校验
+    Verification(String),
+    /// 缓存文件已损坏，无法读取
+    CacheCorrupt(String),
+    /// 读写缓存失败
+    Cache(String),
+    /// 翻译某个章节时出错，`source` 是具体原因
+    Chapter {
+        chapter: String,
+        source: Box<TranslatorError>,
+    },
+}
+
+impl TranslatorError {
+    /// 后端返回的错误：`HttpClient` 产生的错误保留原来的类别，其余归为 `Backend`
+    pub fn from_backend(error: anyhow::Error) -> Self {
+        error
+            .downcast::<TranslatorError>()
+            .unwrap_or_else(|e| TranslatorError::Backend(format!("{e:#}")))
+    }
+
+    /// 附上出错的章节，已经带有章节的错误保持不变
+    pub fn in_chapter(self, chapter: &str) -> Self {
+        match self {
+            TranslatorError::Chapter { .. } => self,
+            source => TranslatorError::Chapter {
+                chapter: chapter.to_string(),
+                source: Box::new(source),
+            },
+        }
+    }
+}
+
+impl fmt::Display for TranslatorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TranslatorError::Auth(message) => write!(f, "authentication failed: {message}"),
+            TranslatorError::RateLimited(message) => write!(f, "rate limited: {message}"),
+            TranslatorError::BadResponse(message) => write!(f, "bad response: {message}"),
+            TranslatorError::Network(message) => write!(f, "network error: {message}"),
+            TranslatorError::Backend(message) => write!(f, "{message}"),
+            TranslatorError::Verification(message) => write!(f, "{message}"),
+            TranslatorError::CacheCorrupt(message) => write!(f, "corrupt cache: {message}"),
+            TranslatorError::Cache(message) => write!(f, "cache error: {message}"),
+            TranslatorError::Chapter { chapter, .. } => write!(f, "failed to translate {chapter}"),
+        }
+    }
+}
+
+impl Error for TranslatorError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TranslatorError::Chapter { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
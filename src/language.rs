@@ -0,0 +1,67 @@
+/// 语言名称与 ISO 639-1 代码的对照表，供需要语言代码的机器翻译接口使用
+const LANGUAGES: &[(&str, &str)] = &[
+    ("arabic", "ar"),
+    ("bulgarian", "bg"),
+    ("chinese", "zh"),
+    ("simplified chinese", "zh"),
+    ("chinese (simplified)", "zh"),
+    ("traditional chinese", "zh-hant"),
+    ("chinese (traditional)", "zh-hant"),
+    ("czech", "cs"),
+    ("danish", "da"),
+    ("dutch", "nl"),
+    ("english", "en"),
+    ("estonian", "et"),
+    ("finnish", "fi"),
+    ("french", "fr"),
+    ("german", "de"),
+    ("greek", "el"),
+    ("hebrew", "he"),
+    ("hindi", "hi"),
+    ("hungarian", "hu"),
+    ("indonesian", "id"),
+    ("italian", "it"),
+    ("japanese", "ja"),
+    ("korean", "ko"),
+    ("latvian", "lv"),
+    ("lithuanian", "lt"),
+    ("norwegian", "nb"),
+    ("polish", "pl"),
+    ("portuguese", "pt"),
+    ("brazilian portuguese", "pt-br"),
+    ("romanian", "ro"),
+    ("russian", "ru"),
+    ("slovak", "sk"),
+    ("slovenian", "sl"),
+    ("spanish", "es"),
+    ("swedish", "sv"),
+    ("thai", "th"),
+    ("turkish", "tr"),
+    ("ukrainian", "uk"),
+    ("vietnamese", "vi"),
+    ("中文", "zh"),
+    ("简体中文", "zh"),
+    ("繁體中文", "zh-hant"),
+    ("日本語", "ja"),
+    ("한국어", "ko"),
+];
+
+/// 把 book.toml 中的语言名称（如 "Chinese"）转换成小写的 ISO 代码（如 "zh"）。
+/// 本身已经是代码的值（如 "zh"、"pt-BR"）原样转成小写返回。
+pub fn iso_code(language: &str) -> Option<String> {
+    let normalized = language.trim().to_lowercase();
+    if let Some((_, code)) = LANGUAGES.iter().find(|(name, _)| *name == normalized) {
+        return Some(code.to_string());
+    }
+
+    let mut parts = normalized.split(['-', '_']);
+    let primary = parts.next()?;
+    let is_code = primary.len() == 2
+        && primary.chars().all(|c| c.is_ascii_alphabetic())
+        && parts.all(|p| !p.is_empty() && p.len() <= 4 && p.chars().all(|c| c.is_ascii_alphanumeric()));
+    if is_code {
+        Some(normalized.replace('_', "-"))
+    } else {
+        None
+    }
+}
@@ -1,5 +1,20 @@
+mod anchors;
+mod backend;
+mod cache;
+mod cache_command;
+mod code_comments;
 mod command_handler;
+mod config;
+mod error;
+mod language;
+mod protect;
+mod segment;
 mod translate_preprocessor;
 
+pub use backend::{TranslationBackend, TranslationRequest};
+pub use cache::Cache;
+pub use cache_command::handle_cache;
 pub use command_handler::*;
-pub use translate_preprocessor::DeepSeekTranslator;
\ No newline at end of file
+pub use config::{BackendKind, OnError, TranslatorConfig};
+pub use error::TranslatorError;
+pub use translate_preprocessor::DeepSeekTranslator;
@@ -1,5 +1,5 @@
 use mdbook_translator::DeepSeekTranslator;
-use mdbook_translator::{make_app, handle_supports, handle_preprocessing};
+use mdbook_translator::{make_app, handle_cache, handle_supports, handle_preprocessing};
 use std::process;
 
 fn main() {
@@ -11,6 +11,11 @@ fn main() {
 
     if let Some(sub_args) = matches.subcommand_matches("supports") {
         handle_supports(&preprocessor, sub_args);
+    } else if let Some(sub_args) = matches.subcommand_matches("cache") {
+        if let Err(e) = handle_cache(&mut preprocessor, sub_args) {
+            eprintln!("{e:?}");
+            process::exit(1);
+        }
     } else if let Err(e) = handle_preprocessing(&mut preprocessor) {
         eprintln!("{e:?}");
         process::exit(1);
@@ -0,0 +1,330 @@
+use mdbook::utils::new_cmark_parser;
+use pulldown_cmark::{CodeBlockKind, Event, LinkType, Tag, TagEnd};
+use std::ops::Range;
+
+use crate::segment::line_start;
+
+/// 被占位符替换掉的一段原文
+pub struct ProtectedSpan {
+    pub original: String,
+    /// 围栏代码块的语言，其它情况为 `None`
+    pub code_lang: Option<String>,
+}
+
+/// 把不应发送给翻译服务的内容替换成占位符后的文本，翻译完成后再原样放回
+pub struct Masked {
+    pub text: String,
+    pub spans: Vec<ProtectedSpan>,
+}
+
+pub fn placeholder(index: usize) -> String {
+    format!("@@MDT{index}@@")
+}
+
+/// 把分块中不应被翻译的内容替换成占位符：代码块、行内代码、链接和图片的地址、
+/// 自动链接、脚注引用、链接引用定义、标题属性以及 `{{#include}}` 等 mdBook 指令
+pub fn mask(chunk: &str) -> Masked {
+    let mut ranges: Vec<(Range<usize>, Option<String>)> = Vec::new();
+    let mut code_blocks: Vec<Range<usize>> = Vec::new();
+    // 正在处理的链接或图片：(链接类型, 整个链接的范围, 链接文字结束的位置)
+    let mut links: Vec<(LinkType, Range<usize>, usize)> = Vec::new();
+    let mut in_code = false;
+
+    for (event, range) in new_cmark_parser(chunk, false).into_offset_iter() {
+        if let Some((_, _, text_end)) = links.last_mut()
+            && !matches!(event, Event::End(TagEnd::Link | TagEnd::Image))
+        {
+            *text_end = (*text_end).max(range.end);
+        }
+
+        match event {
+            Event::Start(Tag::CodeBlock(kind)) => {
+                in_code = true;
+                // 缩进代码块和列表中的代码块从缩进之后开始，缩进也要放进占位符，
+                // 否则译文丢掉行首的空格后，代码块会变成普通段落或跳出列表
+                let range = line_start(chunk, range.start)..range.end;
+                // 缩进代码块的范围包含结尾的换行，占位符不能吞掉它
+                let end = range.start + chunk[range.clone()].trim_end_matches(['\r', '\n']).len();
+                let lang = match kind {
+                    CodeBlockKind::Fenced(info) => info
+                        .split([' ', ',', '{'])
+                        .next()
+                        .filter(|lang| !lang.is_empty())
+                        .map(str::to_string),
+                    CodeBlockKind::Indented => None,
+                };
+                code_blocks.push(range.clone());
+                ranges.push((range.start..end, lang));
+            }
+            Event::End(TagEnd::CodeBlock) => in_code = false,
+            _ if in_code => {}
+            Event::Code(_) | Event::FootnoteReference(_) => ranges.push((range, None)),
+            // 标题末尾的 `{#id .class}` 属性决定锚点，不能被翻译
+            Event::Start(Tag::Heading { id, classes, attrs, .. })
+                if id.is_some() || !classes.is_empty() || !attrs.is_empty() =>
+            {
+                let heading = &chunk[range.clone()];
+                let line = heading[..heading.find('\n').unwrap_or(heading.len())].trim_end();
+                if line.ends_with('}')
+                    && let Some(open) = line.rfind('{')
+                {
+                    ranges.push((range.start + open..range.start + line.len(), None));
+                }
+            }
+            Event::Start(Tag::Link { link_type, .. }) => {
+                links.push((link_type, range.clone(), range.start + 1));
+            }
+            Event::Start(Tag::Image { link_type, .. }) => {
+                links.push((link_type, range.clone(), range.start + 2));
+            }
+            Event::End(TagEnd::Link | TagEnd::Image) => {
+                let Some((link_type, range, text_end)) = links.pop() else {
+                    continue;
+                };
+                match link_type {
+                    // `[text](url)` 和 `[text][label]`：只保护 `]` 之后的地址或标签
+                    LinkType::Inline | LinkType::Reference if chunk[text_end..].starts_with(']') => {
+                        ranges.push((text_end + 1..range.end, None));
+                    }
+                    // `[label]`、`[label][]` 和自动链接：文字本身就是标签或地址，整体保护
+                    _ => ranges.push((range, None)),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // 链接引用定义不会产生事件，逐行查找
+    let mut offset = 0;
+    for line in chunk.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+        if is_link_definition(line) && !code_blocks.iter().any(|r| r.contains(&start)) {
+            let end = start + line.trim_end().len();
+            ranges.push((start + (line.len() - line.trim_start().len())..end, None));
+        }
+    }
+
+    // mdBook 指令会被 links 预处理器展开，路径和参数都不能改动
+    for range in directive_ranges(chunk) {
+        if !code_blocks.iter().any(|r| r.contains(&range.start)) {
+            ranges.push((range, None));
+        }
+    }
+
+    // 链接文字里的行内代码等会嵌套在整体保护的链接中，只保留最外层的范围
+    ranges.sort_by_key(|(range, _)| (range.start, std::cmp::Reverse(range.end)));
+    let mut text = String::with_capacity(chunk.len());
+    let mut spans = Vec::with_capacity(ranges.len());
+    let mut last = 0;
+    for (range, code_lang) in ranges {
+        if range.start < last || range.is_empty() {
+            continue;
+        }
+        text.push_str(&chunk[last..range.start]);
+        text.push_str(&placeholder(spans.len()));
+        spans.push(ProtectedSpan {
+            original: chunk[range.clone()].to_string(),
+            code_lang,
+        });
+        last = range.end;
+    }
+    text.push_str(&chunk[last..]);
+
+    Masked { text, spans }
+}
+
+/// `{{#include file.rs:anchor}}`、`{{#playground}}`、`{{#title}}` 等 mdBook 指令的范围，
+/// 包括用于转义的反斜杠
+fn directive_ranges(chunk: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut offset = 0;
+    while let Some(pos) = chunk[offset..].find("{{#") {
+        let start = offset + pos;
+        let name_len = chunk[start + 3..]
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(chunk.len() - start - 3);
+        match chunk[start..].find("}}") {
+            Some(end) if name_len > 0 => {
+                let start_with_escape = if chunk[..start].ends_with('\\') { start - 1 } else { start };
+                ranges.push(start_with_escape..start + end + 2);
+                offset = start + end + 2;
+            }
+            _ => offset = start + 3,
+        }
+    }
+    ranges
+}
+
+/// `[label]: url "title"` 形式的链接引用定义
+fn is_link_definition(line: &str) -> bool {
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    let Some(rest) = line[indent..].strip_prefix('[') else {
+        return false;
+    };
+    indent <= 3
+        && !rest.starts_with('^')
+        && rest
+            .find("]:")
+            .is_some_and(|end| end > 0 && !rest[end + 2..].trim().is_empty())
+}
+
+/// 如果 `text` 以占位符开头，返回占位符的字节长度
+pub fn placeholder_len(text: &str) -> Option<usize> {
+    let rest = text.strip_prefix("@@MDT")?;
+    let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    (digits > 0 && rest[digits..].starts_with("@@")).then_some("@@MDT".len() + digits + 2)
+}
+
+/// 去掉文本中所有占位符
+pub fn strip_placeholders(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(pos) = rest.find("@@MDT") {
+        output.push_str(&rest[..pos]);
+        let len = placeholder_len(&rest[pos..]).unwrap_or(1);
+        if len == 1 {
+            output.push('@');
+        }
+        rest = &rest[pos + len..];
+    }
+    output.push_str(rest);
+    output
+}
+
+/// 检查译文中 `@@MDT0@@` 到 `@@MDT{count - 1}@@` 各出现一次，且没有其他占位符
+pub fn verify_placeholders(translated: &str, count: usize) -> Result<(), String> {
+    for index in 0..count {
+        let found = translated.matches(&placeholder(index)).count();
+        if found != 1 {
+            return Err(format!("placeholder {} appears {found} times in the translation", placeholder(index)));
+        }
+    }
+    let total = translated.len() - strip_placeholders(translated).len();
+    let expected = (0..count).map(|i| placeholder(i).len()).sum::<usize>();
+    if total != expected {
+        return Err("the translation contains unknown placeholders".to_string());
+    }
+    Ok(())
+}
+
+/// 文本中的占位符个数（占位符从 `@@MDT0@@` 起连续编号）
+pub fn placeholder_count(text: &str) -> usize {
+    (0..).take_while(|&i| text.contains(&placeholder(i))).count()
+}
+
+impl Masked {
+    /// 去掉占位符后是否还有需要翻译的文字
+    pub fn has_text(&self) -> bool {
+        strip_placeholders(&self.text).chars().any(char::is_alphanumeric)
+    }
+
+    /// 把译文中的占位符换回原文，`replacements` 中给出的片段（如翻译过注释的代码块）优先使用
+    pub fn restore(&self, translated: &str, replacements: &[Option<String>]) -> String {
+        let mut restored = translated.to_string();
+        for (index, span) in self.spans.iter().enumerate() {
+            let content = replacements
+                .get(index)
+                .and_then(Option::as_deref)
+                .unwrap_or(&span.original);
+            restored = restored.replacen(&placeholder(index), content, 1);
+        }
+        restored
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(chunk: &str) -> Masked {
+        let masked = mask(chunk);
+        assert_eq!(masked.restore(&masked.text, &[]), chunk);
+        assert_eq!(verify_placeholders(&masked.text, masked.spans.len()), Ok(()));
+        masked
+    }
+
+    #[test]
+    fn masks_code_and_link_targets() {
+        let masked = round_trip(
+            "Run `cargo build` or see [the guide](guide.md#setup \"Guide\") and <https://example.com>.\n\n\
+             ```rust\nlet url = \"https://example.com\";\n```\n",
+        );
+        assert_eq!(
+            masked.text,
+            "Run @@MDT0@@ or see [the guide]@@MDT1@@ and @@MDT2@@.\n\n@@MDT3@@\n"
+        );
+        assert_eq!(masked.spans[3].code_lang.as_deref(), Some("rust"));
+    }
+
+    #[test]
+    fn nested_image_links_keep_the_alt_text() {
+        let masked = round_trip("Click [![the logo](img/logo.png \"Logo\")](https://example.com) to start.\n");
+        assert!(masked.text.contains("the logo"), "{}", masked.text);
+        assert!(!masked.text.contains("logo.png") && !masked.text.contains("example.com"), "{}", masked.text);
+    }
+
+    #[test]
+    fn reference_links_and_definitions() {
+        let masked = round_trip(
+            "See [the docs][docs], [docs][] and [docs].\n\n[docs]: https://example.com/docs \"Docs\"\n",
+        );
+        assert!(masked.text.contains("[the docs]@@MDT0@@"), "{}", masked.text);
+        assert!(!masked.text.contains("example.com"), "{}", masked.text);
+    }
+
+    #[test]
+    fn directives_are_masked_whole() {
+        let masked = round_trip(
+            "Code: {{#include ../src/main.rs:setup}}\n\nWrite \\{{#include file.rs}} to include a file.\n\n\
+             {{#title My Title}}\n\n```text\n{{#include inside.rs}}\n```\n",
+        );
+        assert_eq!(
+            masked.text,
+            "Code: @@MDT0@@\n\nWrite @@MDT1@@ to include a file.\n\n@@MDT2@@\n\n@@MDT3@@\n"
+        );
+        // 转义用的反斜杠与指令一起保护，代码块中的指令随代码块整体保护
+        assert_eq!(masked.spans[1].original, "\\{{#include file.rs}}");
+        assert_eq!(masked.spans[3].original, "```text\n{{#include inside.rs}}\n```");
+    }
+
+    #[test]
+    fn braces_without_a_directive_name_are_text() {
+        let masked = round_trip("Template {{#}} and {{ name }} stay.\n");
+        assert!(masked.spans.is_empty());
+    }
+
+    #[test]
+    fn indented_code_is_masked_with_its_indentation() {
+        let masked = round_trip("Text.\n\n    fn main() {}\n    let x = 1;\n\n- Item:\n\n  ```sh\n  ls\n  ```\n");
+        assert_eq!(masked.text, "Text.\n\n@@MDT0@@\n\n- Item:\n\n@@MDT1@@\n");
+        assert_eq!(masked.spans[0].original, "    fn main() {}\n    let x = 1;");
+        assert_eq!(masked.spans[1].original, "  ```sh\n  ls\n  ```");
+        // 列表标记或引用符号后面的代码块仍从代码块本身开始
+        assert_eq!(round_trip("> ```sh\n> ls\n> ```\n").text, "> @@MDT0@@\n");
+    }
+
+    #[test]
+    fn restore_prefers_replacements() {
+        let masked = mask("Text.\n\n```python\n# comment\n```\n");
+        let replacement = "```python\n# 注释\n```".to_string();
+        assert_eq!(masked.restore(&masked.text, &[Some(replacement)]), "Text.\n\n```python\n# 注释\n```\n");
+    }
+
+    #[test]
+    fn verify_rejects_changed_placeholders() {
+        assert_eq!(verify_placeholders("a @@MDT0@@ b @@MDT1@@", 2), Ok(()));
+        // 重复、缺失和多出的占位符
+        assert!(verify_placeholders("a @@MDT0@@ b @@MDT0@@ @@MDT1@@", 2).is_err());
+        assert!(verify_placeholders("a @@MDT1@@", 2).is_err());
+        assert!(verify_placeholders("a @@MDT0@@ @@MDT1@@ @@MDT7@@", 2).is_err());
+        assert!(verify_placeholders("a @@MDT0@@", 0).is_err());
+    }
+
+    #[test]
+    fn counts_and_strips_placeholders() {
+        assert_eq!(placeholder_count("@@MDT1@@ @@MDT0@@ @@MDT2@@"), 3);
+        assert_eq!(placeholder_count("@@MDT1@@"), 0);
+        assert_eq!(strip_placeholders("a@@MDT0@@b @@MDTx@@ c@@MDT12@@"), "ab @@MDTx@@ c");
+    }
+}
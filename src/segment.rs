@@ -0,0 +1,172 @@
+use mdbook::utils::new_cmark_parser;
+use pulldown_cmark::Event;
+
+const SKIP_MARKER: &str = "<!-- translator: skip -->";
+const END_MARKER: &str = "<!-- translator: end -->";
+
+/// 章节中的一段内容
+pub struct Section<'a> {
+    pub text: &'a str,
+    /// 位于 `<!-- translator: skip -->` 与 `<!-- translator: end -->` 之间的内容为 `false`，原样保留
+    pub translate: bool,
+}
+
+/// 按 `<!-- translator: skip -->` / `<!-- translator: end -->` 标记把章节分成需要翻译和原样保留的几段。
+/// 没有对应结束标记的 skip 一直作用到章节末尾，写在开头即可跳过整个章节。
+pub fn split_skipped_sections(text: &str) -> Vec<Section<'_>> {
+    let mut sections = Vec::new();
+    let mut section_start = 0;
+    let mut skipping = false;
+
+    for (event, range) in new_cmark_parser(text, false).into_offset_iter() {
+        let Event::Html(html) = event else {
+            continue;
+        };
+        let marker = html.trim();
+        if !skipping && marker == SKIP_MARKER {
+            sections.push(Section {
+                text: &text[section_start..range.start],
+                translate: true,
+            });
+            section_start = range.start;
+            skipping = true;
+        } else if skipping && marker == END_MARKER {
+            sections.push(Section {
+                text: &text[section_start..range.end],
+                translate: false,
+            });
+            section_start = range.end;
+            skipping = false;
+        }
+    }
+    sections.push(Section {
+        text: &text[section_start..],
+        translate: !skipping,
+    });
+
+    sections.retain(|section| !section.text.is_empty());
+    sections
+}
+
+/// 按 Markdown 的顶层块切分章节，每个分块不超过 `max_chars` 个字符。
+///
+/// 列表、表格、引用等块不会被拆开；单个块本身超过上限时独占一个分块。
+/// 所有分块按顺序拼接起来与原文完全一致。
+pub fn split_into_chunks(text: &str, max_chars: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut chunk_start = 0;
+    let mut chunk_chars = 0;
+
+    let mut boundaries = block_starts(text);
+    boundaries.push(text.len());
+
+    for block in boundaries.windows(2) {
+        let block_chars = text[block[0]..block[1]].chars().count();
+        if chunk_chars > 0 && chunk_chars + block_chars > max_chars {
+            chunks.push(text[chunk_start..block[0]].to_string());
+            chunk_start = block[0];
+            chunk_chars = 0;
+        }
+        chunk_chars += block_chars;
+    }
+    if chunk_start < text.len() {
+        chunks.push(text[chunk_start..].to_string());
+    }
+    chunks
+}
+
+/// 每个顶层块的起始字节位置
+fn block_starts(text: &str) -> Vec<usize> {
+    let mut starts = Vec::new();
+    let mut depth = 0usize;
+
+    for (event, range) in new_cmark_parser(text, false).into_offset_iter() {
+        match event {
+            Event::Start(_) => {
+                if depth == 0 {
+                    starts.push(line_start(text, range.start));
+                }
+                depth += 1;
+            }
+            Event::End(_) => depth -= 1,
+            _ if depth == 0 => starts.push(line_start(text, range.start)),
+            _ => {}
+        }
+    }
+
+    // 第一个块之前的空行并入第一个分块
+    if let Some(first) = starts.first_mut() {
+        *first = 0;
+    }
+    starts
+}
+
+/// 缩进代码块等块的范围从缩进之后开始，前面只有空白时退回到行首，
+/// 否则缩进会留在上一个分块里
+pub(crate) fn line_start(text: &str, pos: usize) -> usize {
+    let start = text[..pos].rfind('\n').map_or(0, |i| i + 1);
+    if text[start..pos].trim().is_empty() { start } else { pos }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 分块拼接后与原文一致，并且 `block` 完整地落在某一个分块中
+    fn assert_kept_whole(text: &str, block: &str) {
+        let chunks = split_into_chunks(text, 10);
+        assert_eq!(chunks.concat(), text);
+        assert!(chunks.len() > 1, "expected several chunks: {chunks:?}");
+        assert!(chunks.iter().any(|chunk| chunk.contains(block)), "{block:?} was split: {chunks:?}");
+    }
+
+    #[test]
+    fn chunks_concatenate_to_the_input() {
+        let text = "\n\n# Title\n\nFirst paragraph\nwith two lines.\n\n> quote\n\n---\n\nLast paragraph without newline";
+        for max_chars in [1, 10, 40, 4000] {
+            assert_eq!(split_into_chunks(text, max_chars).concat(), text);
+        }
+        assert_eq!(split_into_chunks(text, 4000).len(), 1);
+        assert!(split_into_chunks("", 10).is_empty());
+    }
+
+    #[test]
+    fn nested_lists_are_not_split() {
+        let list = "- first item\n  - nested item\n    - deeper item\n\n  continued paragraph\n- second item\n";
+        assert_kept_whole(&format!("Intro paragraph.\n\n{list}\nOutro paragraph.\n"), list);
+    }
+
+    #[test]
+    fn tables_are_not_split() {
+        let table = "| Name | Value |\n| ---- | ----- |\n| a    | 1     |\n| b    | 2     |\n";
+        assert_kept_whole(&format!("Intro paragraph.\n\n{table}\nOutro paragraph.\n"), table);
+    }
+
+    #[test]
+    fn tilde_fences_are_not_split() {
+        let fence = "~~~rust\nfn main() {}\n\n\nfn other() {}\n~~~\n";
+        assert_kept_whole(&format!("Intro paragraph.\n\n{fence}\nOutro paragraph.\n"), fence);
+    }
+
+    #[test]
+    fn indented_code_is_not_split() {
+        let code = "    let a = 1;\n\n    let b = 2;\n    let c = 3;\n";
+        assert_kept_whole(&format!("Intro paragraph.\n\n{code}\nOutro paragraph.\n"), code);
+    }
+
+    #[test]
+    fn skip_markers_split_sections() {
+        let text = "Translated.\n\n<!-- translator: skip -->\nKept.\n<!-- translator: end -->\n\nTranslated again.\n";
+        let sections = split_skipped_sections(text);
+        let parts = sections.iter().map(|s| (s.text, s.translate)).collect::<Vec<_>>();
+        assert_eq!(
+            parts,
+            [
+                ("Translated.\n\n", true),
+                ("<!-- translator: skip -->\nKept.\n<!-- translator: end -->\n", false),
+                ("\nTranslated again.\n", true),
+            ]
+        );
+        assert_eq!(sections.iter().map(|s| s.text).collect::<String>(), text);
+    }
+}
@@ -1,186 +1,488 @@
-use mdbook::book::{Book, BookItem};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use mdbook::book::{Book, BookItem, Chapter};
 use mdbook::errors::Error;
 use mdbook::preprocess::{Preprocessor, PreprocessorContext};
 use anyhow::Result;
-use reqwest::blocking::Client;
-use serde_json::{json, Value};
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::thread;
 use sha2::{Sha256, Digest};
-use std::{fs, env};
-use std::time::Duration;
+use crate::anchors::{pin_heading_ids, rewrite_fragment_links};
+use crate::backend::{self, TranslationBackend, TranslationRequest};
+use crate::cache::Cache;
+use crate::code_comments::{comment_ranges, replace_comments};
+use crate::config::{OnError, TranslatorConfig};
+use crate::error::TranslatorError;
+use crate::protect::{self, ProtectedSpan};
+use crate::segment::{split_into_chunks, split_skipped_sections};
 
 pub struct DeepSeekTranslator {
-    cache_file: String,
-    pub target_lang: String,
-    pub prompt: String,
-    pub proxy: String,
+    pub config: TranslatorConfig,
+    /// 由 `config.include` 编译而来，为 `None` 时翻译所有章节
+    include: Option<GlobSet>,
+    /// 由 `config.exclude` 编译而来
+    exclude: Option<GlobSet>,
+}
+
+impl Default for DeepSeekTranslator {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl DeepSeekTranslator {
     pub fn new() -> Self {
         Self {
-            cache_file: "deepseek_cache.json".to_string(),
-            target_lang: String::new(),
-            prompt: String::new(),
-            proxy: String::new(),
+            config: TranslatorConfig::default(),
+            include: None,
+            exclude: None,
         }
     }
 
-    pub fn set_language(&mut self, lang: &str) {
-        self.target_lang = lang.to_string();
+    pub fn set_config(&mut self, config: TranslatorConfig) -> Result<()> {
+        self.include = if config.include.is_empty() { None } else { Some(build_glob_set(&config.include)?) };
+        self.exclude = if config.exclude.is_empty() { None } else { Some(build_glob_set(&config.exclude)?) };
+        self.config = config;
+        Ok(())
     }
 
-    pub fn set_prompt(&mut self, prompt: &str) {
-        self.prompt = prompt.to_string();
-    }
-
-    pub fn set_proxy(&mut self, proxy: &str) {
-        self.proxy = proxy.to_string();
-    }
-    
-    // 读取缓存
-    fn load_cache(&self) -> Value {
-        if Path::new(&self.cache_file).exists() {
-            let data = fs::read_to_string(&self.cache_file).expect("failed to read cache file");
-            serde_json::from_str(&data).unwrap_or(json!({}))
-        } else {
-            json!({})
-        }
-    }
-
-    // 写入缓存
-    fn save_cache(&self, cache: &Value) {
-        let data = serde_json::to_string_pretty(cache).expect("failed to serialize cache");
-        fs::write(&self.cache_file, data).expect("failed to write cache file");
+    /// 按 include/exclude 判断章节是否需要翻译，`path` 和 `source_path` 任一匹配即可
+    fn is_selected(&self, chapter: &Chapter) -> bool {
+        let paths = [chapter.path.as_ref(), chapter.source_path.as_ref()];
+        let matches = |set: &GlobSet| paths.iter().flatten().any(|p| set.is_match(p));
+        self.include.as_ref().is_none_or(matches) && !self.exclude.as_ref().is_some_and(matches)
     }
 
     fn hash_key(&self, text: &str) -> String {
         let mut hasher = Sha256::new();
         // 可以把目标语言也加进 hash，支持多语言缓存
         hasher.update(text.as_bytes());
-        hasher.update(self.target_lang.as_bytes());
+        hasher.update(self.config.language.as_bytes());
         format!("{:x}", hasher.finalize())
     }
-}
 
-struct Message {
-    role: String,
-    content: String,
+    /// 生成译文所用的配置的指纹：后端、模型、服务地址或外部命令、系统提示和用户提示，
+    /// 任一改变后旧译文即视为过期
+    fn cache_fingerprint(&self, backend: &dyn TranslationBackend) -> String {
+        let mut hasher = Sha256::new();
+        for part in [backend.name(), backend.model(), backend::SYSTEM_PROMPT, &self.config.source_language, &self.config.prompt] {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+        // 只在设置了 base-url 或 backend-command 时加入，使用默认地址生成的缓存不会因此过期
+        let endpoint = [self.config.base_url(), self.config.backend_command.as_deref()];
+        for part in endpoint.into_iter().flatten().filter(|part| !part.trim().is_empty()) {
+            hasher.update(part.trim().as_bytes());
+            hasher.update([0]);
+        }
+        format!("{:x}", hasher.finalize())
+    }
 }
 
+/// 译文中的占位符对不上时，最多请求的次数
+const MAX_ATTEMPTS: usize = 3;
+
+/// `on-error = "fallback-annotated"` 时加在未翻译的原文前的提示
+const UNTRANSLATED_NOTE: &str =
+    "> **Untranslated:** this section could not be translated and is shown in the original language.";
+
+/// 章节名和分部标题翻译失败时，在错误和汇总中显示的位置
+const TITLES: &str = "chapter titles (SUMMARY.md)";
+
 impl DeepSeekTranslator {
-    pub fn translate_text(
+    /// 请求翻译服务（不经过缓存），`verify` 不通过时重新请求
+    fn request_verified(
         &self,
-        client: &Client,
-        api_key: &str,
+        backend: &dyn TranslationBackend,
         text: &str,
-        cache: &mut Value,
-    ) -> String {
-        let key = self.hash_key(text);
-        // 使用原文作为 key，简单去重
-        if let Some(cached) = cache.get(&key) {
-            let mut print_cached = String::new();
-            if let Value::String(cached_str) = cached {
-                if cached_str.chars().count() > 100 {
-                    print_cached.push_str(&cached_str.chars().take(100).collect::<String>());
-                    print_cached.push_str("...");
+        prompt: &str,
+        chapter_path: &str,
+        verify: impl Fn(&str) -> Result<(), String>,
+    ) -> Result<String, TranslatorError> {
+        let request = TranslationRequest {
+            text,
+            source_lang: &self.config.source_language,
+            target_lang: &self.config.language,
+            prompt,
+            chapter_path,
+        };
+
+        let mut last_reason = String::new();
+        for attempt in 1..=MAX_ATTEMPTS {
+            eprintln!("\x1b[38;2;214;200;75;1mRequesting {} API, please wait patiently\x1b[0m", backend.name());
+            let translated = backend.translate(&request).map_err(TranslatorError::from_backend)?;
+
+            // 空的译文会让这段内容从章节中消失，与校验失败一样重新请求
+            let checked = if translated.trim().is_empty() {
+                Err(format!("the {} API returned an empty translation", backend.name()))
+            } else {
+                verify(&translated)
+            };
+            if let Err(reason) = checked {
+                eprintln!(
+                    "\x1b[33;1mWarning:\x1b[0m attempt {attempt}/{MAX_ATTEMPTS} in {chapter_path}: {reason}"
+                );
+                last_reason = reason;
+                continue;
+            }
+
+            eprintln!("\x1b[38;2;214;200;75;1mRequest succeed, translated:\x1b[0m {:?}", preview(&translated));
+
+            return Ok(translated);
+        }
+
+        Err(TranslatorError::Verification(format!(
+            "no usable {} translation after {MAX_ATTEMPTS} attempts: {last_reason}",
+            backend.name()
+        )))
+    }
+
+    /// 这个错误是否需要中止构建。缓存读写失败与翻译服务无关，不受 `on-error` 影响
+    fn aborts_on(&self, error: &TranslatorError) -> bool {
+        let cache_error = matches!(error, TranslatorError::Cache(_) | TranslatorError::CacheCorrupt(_));
+        self.config.on_error == OnError::Fail || cache_error
+    }
+
+    /// 这个片段的错误是否需要中止构建。注释的翻译是可选的，失败时保留原来的代码块，只有缓存错误会中止构建
+    fn segment_aborts_on(&self, segment: &Segment, error: &TranslatorError) -> bool {
+        if segment.comment_lines.is_some() {
+            matches!(error, TranslatorError::Cache(_) | TranslatorError::CacheCorrupt(_))
+        } else {
+            self.aborts_on(error)
+        }
+    }
+
+    /// 按 `on-error` 处理翻译失败：`fail` 时返回带有位置的错误，否则打印警告并记录位置，由调用方保留原文
+    fn keep_source(
+        &self,
+        error: TranslatorError,
+        location: &str,
+        untranslated: &mut Vec<String>,
+    ) -> Result<(), TranslatorError> {
+        if self.aborts_on(&error) {
+            return Err(error.in_chapter(location));
+        }
+        eprintln!("\x1b[33;1mWarning:\x1b[0m keeping the source text in {location}: {error}");
+        untranslated.push(location.to_string());
+        Ok(())
+    }
+
+    /// 翻译章节名和 SUMMARY 中的分部标题。已缓存的标题直接使用，
+    /// 其余标题合并成一次请求（每行一个），保证译名前后一致，结果按标题分别缓存
+    fn translate_book_titles(
+        &self,
+        backend: &dyn TranslationBackend,
+        book: &mut Book,
+        cache: &mut Cache,
+    ) -> Result<(), TranslatorError> {
+        let titles = book_titles(book);
+
+        let mut translations: HashMap<String, String> = HashMap::new();
+        let mut pending = Vec::new();
+        for title in titles {
+            match cache.get(&self.hash_key(&title))? {
+                Some(cached) => {
+                    translations.insert(title, cached);
+                }
+                None => pending.push(title),
+            }
+        }
+
+        if !pending.is_empty() {
+            eprintln!();
+            eprintln!("\x1b[32;1mProcessing titles:\x1b[0m  \x1b[1m{}\x1b[0m", pending.len());
+
+            let prompt = format!(
+                "{}\n\nEach line is a separate chapter title. Translate every line on its own and keep exactly {} lines, in the same order.",
+                self.config.prompt,
+                pending.len()
+            );
+            let translated = self.request_verified(backend, &pending.join("\n"), prompt.trim(), "SUMMARY.md", |t| {
+                let lines = t.trim().lines().count();
+                if lines == pending.len() {
+                    Ok(())
                 } else {
-                    print_cached.push_str(cached_str);
+                    Err(format!("expected {} translated titles but got {lines}", pending.len()))
+                }
+            })?;
+
+            for (title, line) in pending.into_iter().zip(translated.trim().lines()) {
+                let line = line.trim();
+                if !line.is_empty() {
+                    cache.insert(&self.hash_key(&title), &title, line)?;
+                    translations.insert(title, line.to_string());
                 }
             }
-            eprintln!("\x1b[38;2;38;188;213;1mCache hit:\x1b[0m {:?}", print_cached);
-            return cached.as_str().unwrap_or("").to_string();
-        }
-
-        let url = "https://api.deepseek.com/v1/chat/completions";
-        let mut messages = Vec::from([
-            Message {
-                role: "system".to_string(),
-                content: "你是专业技术文档翻译助手，保留代码、命令，术语翻译尽量遵循社区的常见用法。如果有不理解的术语，保持原文。".to_string(),
-            },
-            Message {
-                role: "user".to_string(),
-                content: format!("Translate the following text into {}:\n\n{}", self.target_lang, text).to_string(),
-            }
-        ]);
-        if !self.prompt.is_empty() {
-            messages.push(Message {
-                role: "user".to_string(),
-                content: self.prompt.to_string(),
-            });
         }
 
-        let body = json!({
-            "model": "deepseek-chat",
-            "messages": messages.iter().map(|m| json!({
-                "role": m.role,
-                "content": m.content,
-            })).collect::<Vec<_>>(),
+        let translate = |title: &mut String| {
+            if let Some(translated) = translations.get(title.as_str()) {
+                *title = translated.clone();
+            }
+        };
+        book.for_each_mut(|item| match item {
+            BookItem::Chapter(chapter) => {
+                translate(&mut chapter.name);
+                chapter.parent_names.iter_mut().for_each(translate);
+            }
+            BookItem::PartTitle(title) => translate(title),
+            BookItem::Separator => {}
         });
+        Ok(())
+    }
 
-        eprintln!("\x1b[38;2;214;200;75;1mRequesting Deepseek API, please wait patiently\x1b[0m");
-        let resp = client
-            .post(url)
-            .header("Authorization", format!("Bearer {}", api_key))
-            .json(&body)
-            .send()
-            .expect("failed to send request to deepseek api");
-
-        let json_resp: serde_json::Value =
-            resp.json().expect("failed to parse response from deepseek api");
-
-        let translated = json_resp["choices"][0]["message"]["content"]
-            .as_str()
-            .unwrap_or("")
-            .to_string();
-
-        if !translated.is_empty() {
-            // 写入缓存
-            cache[&key] = json!(translated);
-            // 保存缓存
-            self.save_cache(&cache);
-        }
-
-        let mut print_translated = String::new();
-            if translated.chars().count() > 100 {
-                print_translated.push_str(&translated.chars().take(100).collect::<String>());
-                print_translated.push_str("...");
-            } else {
-                print_translated.push_str(&translated);
+    /// 翻译书中的所有片段：先查缓存，其余片段交给 `concurrency` 个线程并发请求，
+    /// 返回每个 key 的译文，或按 `on-error` 可以保留原文的错误
+    fn translate_segments(
+        &self,
+        backend: &dyn TranslationBackend,
+        segments: Vec<Segment>,
+        cache: &mut Cache,
+    ) -> Result<Translations, TranslatorError> {
+        let mut translations = Translations::new();
+        let mut seen = HashSet::new();
+        let mut pending = Vec::new();
+        let mut location = "";
+        for segment in &segments {
+            if !seen.insert(segment.key.as_str()) {
+                continue;
             }
+            if segment.location != location {
+                location = &segment.location;
+                eprintln!();
+                eprintln!("\x1b[32;1mProcessing chapter:\x1b[0m  \x1b[1m{location}\x1b[0m");
+            }
+            if let Some(cached) = cache.get(&segment.key)? {
+                match segment.verify(&cached) {
+                    Ok(()) => {
+                        eprintln!("\x1b[38;2;38;188;213;1mCache hit:\x1b[0m {:?}", preview(&cached));
+                        translations.insert(segment.key.clone(), Ok(cached));
+                        continue;
+                    }
+                    Err(reason) => {
+                        eprintln!("\x1b[33;1mWarning:\x1b[0m ignoring cached translation: {reason}");
+                    }
+                }
+            }
+            pending.push(segment);
+        }
+        if pending.is_empty() {
+            return Ok(translations);
+        }
 
-        eprintln!("\x1b[38;2;214;200;75;1mRequest succeed, translated:\x1b[0m {:?}", print_translated);
+        let workers = self.config.concurrency.min(pending.len());
+        eprintln!();
+        eprintln!(
+            "\x1b[32;1mTranslating:\x1b[0m  \x1b[1m{} segments with {workers} workers\x1b[0m",
+            pending.len()
+        );
 
-        translated
+        // 每个线程依次领取下一个片段；出现需要中止构建的错误后不再领取新的片段
+        let next = AtomicUsize::new(0);
+        let aborted = AtomicBool::new(false);
+        let cache = Mutex::new(cache);
+        let results = Mutex::new(Vec::new());
+        thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| {
+                    while !aborted.load(Ordering::Relaxed) {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(segment) = pending.get(index) else { break };
+                        let result = self.translate_segment(backend, segment, &cache);
+                        if result.as_ref().is_err_and(|e| self.segment_aborts_on(segment, e)) {
+                            aborted.store(true, Ordering::Relaxed);
+                        }
+                        lock(&results).push((index, result));
+                    }
+                });
+            }
+        });
+
+        // 按书中的顺序报告第一个需要中止构建的错误
+        let mut results = results.into_inner().unwrap_or_else(PoisonError::into_inner);
+        results.sort_by_key(|(index, _)| *index);
+        for (index, result) in results {
+            let segment = pending[index];
+            if let Err(e) = &result
+                && self.segment_aborts_on(segment, e)
+            {
+                return Err(e.clone().in_chapter(&segment.location));
+            }
+            translations.insert(segment.key.clone(), result);
+        }
+        Ok(translations)
     }
 
-    fn walk_items(&self, client: &Client, api_key: &str, items: &mut Vec<BookItem>, cache: &mut Value) {
+    /// 请求一个片段的译文，拿到后立即写入缓存，构建中途失败时已完成的译文不会丢失
+    fn translate_segment(
+        &self,
+        backend: &dyn TranslationBackend,
+        segment: &Segment,
+        cache: &Mutex<&mut Cache>,
+    ) -> Result<String, TranslatorError> {
+        let translated = self.request_verified(backend, &segment.text, &self.config.prompt, &segment.chapter_path, |t| {
+            segment.verify(t)
+        })?;
+        lock(cache).insert(&segment.key, &segment.text, &translated)?;
+        Ok(translated)
+    }
+
+    /// 用翻译好的片段拼出分块的译文：代码块原样放回，需要翻译注释的代码块换上注释的译文
+    fn assemble_chunk(&self, chunk: &str, translations: &Translations) -> Result<String, TranslatorError> {
+        let masked = protect::mask(chunk);
+        let translated = if masked.has_text() {
+            lookup(translations, &self.hash_key(&masked.text))?
+        } else {
+            masked.text.clone()
+        };
+
+        let mut replacements = Vec::new();
+        for span in &masked.spans {
+            let replacement = match self.span_comments(span) {
+                Some((ranges, text)) => {
+                    code_comments_replacement(&span.original, &ranges, lookup(translations, &self.hash_key(&text)))
+                }
+                None => None,
+            };
+            replacements.push(replacement);
+        }
+
+        Ok(masked.restore(&translated, &replacements))
+    }
+
+    fn walk_items(
+        &self,
+        items: &mut [BookItem],
+        translations: &Translations,
+        anchors: &mut HashMap<PathBuf, HashMap<String, String>>,
+        untranslated: &mut Vec<String>,
+    ) -> Result<(), TranslatorError> {
         for item in items.iter_mut() {
-            match item {
-                BookItem::Chapter(chapter) => {
-                    let chapter_num = match &chapter.number {
-                        Some(num) => num.to_string(),
-                        None => "".to_string(),
-                    };
+            if let BookItem::Chapter(chapter) = item {
+                let location = chapter_location(chapter);
+
+                if !self.is_selected(chapter) {
+                    eprintln!("\x1b[38;2;38;188;213;1mSkipped by include/exclude:\x1b[0m {location}");
+                    self.walk_items(&mut chapter.sub_items, translations, anchors, untranslated)?;
+                    continue;
+                }
 
-                    eprintln!();
-                    eprintln!("\x1b[32;1mProcessing chapter:\x1b[0m  \x1b[1m{}{}\x1b[0m", &chapter_num, &chapter.name);
-
-                    let chunks = split_into_chunks(&chapter.content, 4000);
-                    chapter.content = "".to_string();
-                    chunks.into_iter().for_each(|chunk| {
-                        let translated = self.translate_text(client, api_key, &chunk, cache);
-                        chapter.content.push_str(&translated);
-                        // 如果是以```结尾，则加上一个换行符
-                        if translated.ends_with("```") {
-                            chapter.content.push_str("\n\n");
+                let source = std::mem::take(&mut chapter.content);
+                for (chunk, translate) in chapter_chunks(&source) {
+                    if !translate || chunk.trim().is_empty() {
+                        chapter.content.push_str(&chunk);
+                        continue;
+                    }
+                    let translated = match self.assemble_chunk(&chunk, translations) {
+                        Ok(translated) => translated,
+                        Err(e) => {
+                            self.keep_source(e, &location, untranslated)?;
+                            match self.config.on_error {
+                                OnError::FallbackAnnotated => {
+                                    format!("{UNTRANSLATED_NOTE}\n\n{}", chunk.trim_start_matches(['\r', '\n']))
+                                }
+                                _ => chunk.clone(),
+                            }
                         }
-                    });
-                    self.walk_items(client, api_key, &mut chapter.sub_items, cache);
+                    };
+                    // 译文首尾的空行常被模型丢掉，按原文补回，保证块与块之间的分隔不变
+                    let body = chunk.trim_start_matches(['\r', '\n']);
+                    chapter.content.push_str(&chunk[..chunk.len() - body.len()]);
+                    chapter.content.push_str(translated.trim_start_matches(['\r', '\n']).trim_end());
+                    chapter.content.push_str(&chunk[chunk.trim_end().len()..]);
+                }
+
+                if let Some(style) = self.config.stable_anchors {
+                    let (content, renamed) = pin_heading_ids(&source, &chapter.content, style);
+                    chapter.content = content;
+                    if let Some(path) = &chapter.path {
+                        anchors.insert(path.clone(), renamed);
+                    }
+                }
+
+                self.walk_items(&mut chapter.sub_items, translations, anchors, untranslated)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl DeepSeekTranslator {
+    /// 这种语言的代码块是否需要翻译注释
+    fn wants_comments(&self, lang: &str) -> bool {
+        self.config.translate_code_comments.iter().any(|l| l.eq_ignore_ascii_case(lang))
+    }
+
+    /// 需要翻译注释的代码块中的注释位置，以及合并成一次请求的注释文字
+    fn span_comments(&self, span: &ProtectedSpan) -> Option<(Vec<Range<usize>>, String)> {
+        let lang = span.code_lang.as_deref().filter(|lang| self.wants_comments(lang))?;
+        let ranges = comment_ranges(&span.original, lang);
+        if ranges.is_empty() {
+            return None;
+        }
+        let text = comment_text(&span.original, &ranges);
+        Some((ranges, text))
+    }
+
+    /// 按章节顺序列出书中需要翻译的全部片段，同一段原文可能出现多次
+    fn book_segments(&self, book: &Book) -> Vec<Segment> {
+        let mut segments = Vec::new();
+        for item in book.iter() {
+            let BookItem::Chapter(chapter) = item else { continue };
+            if !self.is_selected(chapter) {
+                continue;
+            }
+            let location = chapter_location(chapter);
+            let chapter_path = chapter
+                .path
+                .as_ref()
+                .map(|p| p.display().to_string())
+                .unwrap_or_default();
+            let mut push = |text: String, placeholders: usize, comment_lines: Option<usize>| {
+                segments.push(Segment {
+                    key: self.hash_key(&text),
+                    text,
+                    location: location.clone(),
+                    chapter_path: chapter_path.clone(),
+                    placeholders,
+                    comment_lines,
+                })
+            };
+
+            for (chunk, translate) in chapter_chunks(&chapter.content) {
+                if !translate || chunk.trim().is_empty() {
+                    continue;
+                }
+                // 代码块先替换成占位符，不发送给翻译服务，拼回章节时再原样放回
+                let masked = protect::mask(&chunk);
+                if masked.has_text() {
+                    push(masked.text.clone(), masked.spans.len(), None);
+                }
+                for span in &masked.spans {
+                    if let Some((ranges, text)) = self.span_comments(span) {
+                        push(text, 0, Some(ranges.len()));
+                    }
                 }
-                _ => {}
             }
         }
+        segments
+    }
+
+    /// 按 `walk_items` 和 `translate_book_titles` 的方式遍历书籍，返回翻译这本书会用到的全部缓存 key
+    pub fn cache_keys(&self, book: &Book) -> HashSet<String> {
+        let mut keys = self
+            .book_segments(book)
+            .into_iter()
+            .map(|segment| segment.key)
+            .collect::<HashSet<_>>();
+
+        if self.config.translate_titles {
+            keys.extend(book_titles(book).iter().map(|title| self.hash_key(title)));
+        }
+        keys
     }
 }
 
@@ -189,61 +491,329 @@ impl Preprocessor for DeepSeekTranslator {
         "translator"
     }
 
-    fn run(&self, _ctx: &PreprocessorContext, mut book: Book) -> Result<Book, Error> {
-        let api_key = env::var("DEEPSEEK_API_KEY")
-            .expect("请在环境变量中设置 DEEPSEEK_API_KEY");
+    fn run(&self, ctx: &PreprocessorContext, mut book: Book) -> Result<Book, Error> {
+        // 没有配置 before/after 时 mdBook 按名称排序，links 先于本插件运行
+        if self.config.before.iter().any(|name| name == "links") {
+            eprintln!(
+                "Running before the links preprocessor: {{{{#include}}}} and other directives are kept verbatim, included files are not translated"
+            );
+        } else {
+            eprintln!(
+                "Running after the links preprocessor: included files are translated together with the chapter"
+            );
+        }
+
+        let backend = backend::create_backend(&self.config, &ctx.root)?;
+        let fingerprint = self.cache_fingerprint(backend.as_ref());
+        let mut cache = Cache::open(&ctx.root, &self.config, backend.as_ref(), fingerprint)?;
+        eprintln!("Using translation cache {}", cache.path().display());
 
-        // eprintln!("api_key: {:?}", api_key);
+        let segments = self.book_segments(&book);
+        let translations = self.translate_segments(backend.as_ref(), segments, &mut cache)?;
 
-        let proxy = &self.proxy;
-        let mut client_builder = Client::builder()
-                    .timeout(Duration::from_secs(600)); // 显式设置超时
-        
-        if !proxy.is_empty() {
-            client_builder = client_builder.proxy(reqwest::Proxy::all(proxy)?);
+        let mut anchors = HashMap::new();
+        let mut untranslated = Vec::new();
+        self.walk_items(&mut book.sections, &translations, &mut anchors, &mut untranslated)?;
+
+        if self.config.translate_titles
+            && let Err(e) = self.translate_book_titles(backend.as_ref(), &mut book, &mut cache)
+        {
+            // 标题中放不下提示，失败时只保留原来的标题
+            self.keep_source(e, TITLES, &mut untranslated)?;
         }
-        
-        let client = client_builder.build()?;
-        let mut cache = self.load_cache();
 
-        self.walk_items(&client, &api_key, &mut book.sections, &mut cache);
+        // 所有章节的锚点确定后，再统一修正指向它们的链接
+        if self.config.stable_anchors.is_some() {
+            book.for_each_mut(|item| {
+                if let BookItem::Chapter(chapter) = item
+                    && let Some(path) = &chapter.path
+                {
+                    chapter.content = rewrite_fragment_links(&chapter.content, path, &anchors);
+                }
+            });
+        }
 
-        // 保存缓存
-        // self.save_cache(&cache);
+        if cache.stale() > 0 {
+            if self.config.invalidate_stale_cache {
+                eprintln!(
+                    "Retranslated {} cached segments that were produced with a different backend, model, endpoint or prompt",
+                    cache.stale()
+                );
+            } else {
+                eprintln!(
+                    "\x1b[33;1mWarning:\x1b[0m {} cached translations were produced with a different backend, model, endpoint or prompt; set `invalidate-stale-cache = true` to retranslate them",
+                    cache.stale()
+                );
+            }
+        }
+
+        if !untranslated.is_empty() {
+            eprintln!();
+            eprintln!(
+                "\x1b[33;1mWarning:\x1b[0m {} segments could not be translated and were left in the source language:",
+                untranslated.len()
+            );
+            let mut locations: Vec<(&str, usize)> = Vec::new();
+            for location in &untranslated {
+                match locations.last_mut() {
+                    Some((last, count)) if *last == location => *count += 1,
+                    _ => locations.push((location, 1)),
+                }
+            }
+            for (location, count) in locations {
+                eprintln!("  {location}: {count}");
+            }
+        }
 
         Ok(book)
     }
 }
 
-fn split_into_chunks(text: &str, max_chars: usize) -> Vec<String> {
-    let mut chunks = Vec::new();
-    let mut buffer = String::new();
-    let mut is_in_code = false;
+/// 需要请求翻译服务的一段文字：分块中代码块以外的部分，或一个代码块中的全部行注释
+struct Segment {
+    key: String,
+    text: String,
+    /// 所在的章节，用于日志和错误信息
+    location: String,
+    chapter_path: String,
+    /// 原文中占位符的个数，译文必须保留每一个
+    placeholders: usize,
+    /// 代码注释片段的行数（每条注释一行），译文行数不同时无法放回代码块
+    comment_lines: Option<usize>,
+}
 
-    text.lines().into_iter().for_each(|line| {
-        if line.is_empty() {
-            buffer.push_str("\n\n");
-            return;
+impl Segment {
+    /// 检查译文能否放回原处，不通过的译文不会被缓存或使用
+    fn verify(&self, translated: &str) -> Result<(), String> {
+        protect::verify_placeholders(translated, self.placeholders)?;
+        if let Some(expected) = self.comment_lines {
+            let lines = translated.trim().lines().count();
+            if lines != expected {
+                return Err(format!("expected {expected} translated comments but got {lines}"));
+            }
         }
-        if line.starts_with("```") {
-            buffer.push_str(line);
-            buffer.push_str("\n");
-            is_in_code = !is_in_code;
-            return;
+        Ok(())
+    }
+}
+
+/// 每个片段 key 对应的译文，或按 `on-error` 可以保留原文的错误
+type Translations = HashMap<String, Result<String, TranslatorError>>;
+
+fn lookup(translations: &Translations, key: &str) -> Result<String, TranslatorError> {
+    translations
+        .get(key)
+        .cloned()
+        .unwrap_or_else(|| Err(TranslatorError::Backend("the segment was not translated".to_string())))
+}
+
+/// 用翻译好的注释替换代码块中的注释。译文的行数在请求时已经校验过；注释没能翻译时保留原来的代码块，
+/// 不影响分块中其他文字的译文
+fn code_comments_replacement(
+    block: &str,
+    ranges: &[Range<usize>],
+    translated: Result<String, TranslatorError>,
+) -> Option<String> {
+    match translated {
+        Ok(translated) => Some(replace_comments(block, ranges, &translated.trim().lines().collect::<Vec<_>>())),
+        Err(e) => {
+            eprintln!("\x1b[33;1mWarning:\x1b[0m failed to translate code comments, keeping the original code block: {e}");
+            None
         }
-        if is_in_code || (buffer.len() + line.len() < max_chars){
-            buffer.push_str(&line);
-            buffer.push_str("\n");
-        } else {
-            chunks.push(buffer.clone());
-            buffer.clear();
-            buffer.push_str(&line);
-            buffer.push_str("\n");
+    }
+}
+
+/// 日志和错误信息中的章节，如 `1.2.Intro (intro.md)`
+fn chapter_location(chapter: &Chapter) -> String {
+    let number = chapter.number.as_ref().map(|n| n.to_string()).unwrap_or_default();
+    let path = chapter.path.as_ref().map(|p| p.display().to_string()).unwrap_or_default();
+    format!("{number}{} ({path})", chapter.name)
+}
+
+/// 其他线程 panic 后锁中的数据仍然可用，panic 本身会在 `thread::scope` 结束时传播
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// 按跳过标记和块边界把章节切分成分块，`bool` 表示分块是否需要翻译
+fn chapter_chunks(source: &str) -> Vec<(String, bool)> {
+    split_skipped_sections(source)
+        .into_iter()
+        .flat_map(|section| match section.translate {
+            true => split_into_chunks(section.text, 4000)
+                .into_iter()
+                .map(|chunk| (chunk, true))
+                .collect::<Vec<_>>(),
+            false => vec![(section.text.to_string(), false)],
+        })
+        .collect()
+}
+
+/// 书中所有不重复的章节名和分部标题
+fn book_titles(book: &Book) -> Vec<String> {
+    let mut titles: Vec<String> = Vec::new();
+    for item in book.iter() {
+        let title = match item {
+            BookItem::Chapter(chapter) => &chapter.name,
+            BookItem::PartTitle(title) => title,
+            BookItem::Separator => continue,
+        };
+        if !title.trim().is_empty() && !titles.contains(title) {
+            titles.push(title.clone());
+        }
+    }
+    titles
+}
+
+/// 代码块中的行注释，每行一条，合并成一次请求
+fn comment_text(block: &str, ranges: &[Range<usize>]) -> String {
+    ranges
+        .iter()
+        .map(|range| &block[range.clone()])
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// 日志中只显示前 100 个字符
+fn preview(text: &str) -> String {
+    if text.chars().count() > 100 {
+        format!("{}...", text.chars().take(100).collect::<String>())
+    } else {
+        text.to_string()
+    }
+}
+
+fn build_glob_set(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern)?);
+    }
+    Ok(builder.build()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::BackendKind;
+
+    /// 按 `reply` 改写原文的后端，模拟模型对格式的改动
+    struct FakeBackend(fn(&str) -> String);
+
+    impl TranslationBackend for FakeBackend {
+        fn name(&self) -> &str {
+            "Fake"
         }
-    });
-    if !buffer.is_empty() {
-        chunks.push(buffer.clone());
-        buffer.clear();
+
+        fn translate(&self, request: &TranslationRequest) -> Result<String> {
+            Ok((self.0)(request.text))
+        }
+    }
+
+    fn config() -> TranslatorConfig {
+        TranslatorConfig {
+            language: "Chinese".to_string(),
+            ..TranslatorConfig::default()
+        }
+    }
+
+    /// 与 `run` 一样翻译一个只有一章的书，返回章节内容和未翻译的位置
+    fn translate_chapter(
+        config: TranslatorConfig,
+        backend: &dyn TranslationBackend,
+        content: &str,
+    ) -> Result<(String, Vec<String>), TranslatorError> {
+        let root = tempfile::tempdir().unwrap();
+        let mut translator = DeepSeekTranslator::new();
+        translator.set_config(config).unwrap();
+        let mut book = Book::new();
+        book.push_item(Chapter::new("Intro", content.to_string(), "intro.md", Vec::new()));
+
+        let fingerprint = translator.cache_fingerprint(backend);
+        let mut cache = Cache::open(root.path(), &translator.config, backend, fingerprint)?;
+        let translations = translator.translate_segments(backend, translator.book_segments(&book), &mut cache)?;
+        let mut untranslated = Vec::new();
+        translator.walk_items(&mut book.sections, &translations, &mut HashMap::new(), &mut untranslated)?;
+
+        let BookItem::Chapter(chapter) = &book.sections[0] else { unreachable!() };
+        Ok((chapter.content.clone(), untranslated))
+    }
+
+    #[test]
+    fn code_blocks_survive_a_backend_that_trims_lines() {
+        let backend = FakeBackend(|text| text.lines().map(str::trim).collect::<Vec<_>>().join("\n"));
+        // 长段落让缩进代码块从新的分块开始
+        let long = "Long paragraph. ".repeat(300);
+        let content = format!(
+            "{}\n\n    fn main() {{}}\n    let x = 1;\n\n- Item:\n\n  ```sh\n  ls\n  ```\n- Next item.\n",
+            long.trim()
+        );
+        let (translated, untranslated) = translate_chapter(config(), &backend, &content).unwrap();
+        assert_eq!(translated, content);
+        assert!(untranslated.is_empty());
+    }
+
+    #[test]
+    fn failed_comment_translations_keep_the_original_code_block() {
+        // 段落正常翻译，注释的译文总是合并成一行，行数校验失败
+        let backend = FakeBackend(|text| {
+            if text.contains('\n') && !text.contains("@@MDT") {
+                text.to_uppercase().replace('\n', " ")
+            } else {
+                text.to_uppercase()
+            }
+        });
+        let content = "Some text.\n\n```rust\n// first\nlet x = 1;\n// second\n```\n";
+        for on_error in [OnError::Fail, OnError::Fallback] {
+            let config = TranslatorConfig {
+                on_error,
+                translate_code_comments: vec!["rust".to_string()],
+                ..config()
+            };
+            let (translated, untranslated) = translate_chapter(config, &backend, content).unwrap();
+            assert_eq!(translated, content.replace("Some text.", "SOME TEXT."));
+            assert!(untranslated.is_empty());
+        }
+    }
+
+    #[test]
+    fn cache_keys_match_the_entries_a_build_writes() {
+        let root = tempfile::tempdir().unwrap();
+        let ctx: PreprocessorContext = serde_json::from_value(serde_json::json!({
+            "root": root.path(),
+            "config": { "book": { "title": "Test", "src": "src" } },
+            "renderer": "html",
+            "mdbook_version": mdbook::MDBOOK_VERSION,
+        }))
+        .unwrap();
+        let mut translator = DeepSeekTranslator::new();
+        translator
+            .set_config(TranslatorConfig {
+                backend: BackendKind::Mock,
+                translate_code_comments: vec!["rust".to_string()],
+                ..config()
+            })
+            .unwrap();
+
+        let book = |paragraph: &str| {
+            let content = format!("# Intro\n\n{paragraph}\n\n```rust\n// A comment\nlet x = 1;\n```\n");
+            let mut intro = Chapter::new("Intro", content, "intro.md", Vec::new());
+            let sub = Chapter::new("Sub", "Sub text.\n".to_string(), "sub.md", vec!["Intro".to_string()]);
+            intro.sub_items.push(BookItem::Chapter(sub));
+            let mut book = Book::new();
+            book.push_item(BookItem::Chapter(intro));
+            book
+        };
+        translator.run(&ctx, book("Hello world.")).unwrap();
+
+        // 正文、注释和标题的 key 都与缓存中的条目一一对应
+        let path = root.path().join(".translator-cache/zh.json");
+        let store = crate::cache::open_store(&path, translator.config.cache_store, None).unwrap();
+        let cached = store.records().unwrap().into_iter().map(|record| record.key).collect::<HashSet<_>>();
+        assert_eq!(cached.len(), 5, "{cached:?}");
+        assert_eq!(translator.cache_keys(&book("Hello world.")), cached);
+
+        // 改动一个段落后，只有这一章旧的正文不再使用
+        let keys = translator.cache_keys(&book("Goodbye world."));
+        assert_eq!(cached.difference(&keys).count(), 1);
+        assert_eq!(keys.difference(&cached).count(), 1);
     }
-    chunks
 }
@@ -0,0 +1,136 @@
+use mdbook::book::{Book, BookItem, Chapter, SectionNumber};
+use mdbook::preprocess::{Preprocessor, PreprocessorContext};
+use mdbook_translator::{DeepSeekTranslator, TranslatorConfig};
+use serde_json::{Value, json};
+use std::fs;
+use std::path::Path;
+
+const INTRO: &str = "# Getting Started
+
+Hello world, this is the **first** chapter.
+
+```rust
+fn main() {
+    println!(\"Hello, world!\");
+}
+```
+
+See [the setup guide](setup.md#install) and run `cargo build`.
+
+{{#include ../listings/example.rs:main}}
+";
+
+const SETUP: &str = "## Install
+
+Download the binary from <https://example.com/download>.
+";
+
+fn book() -> Book {
+    let mut intro = Chapter::new("Introduction", INTRO.to_string(), "intro.md", Vec::new());
+    intro.number = Some(SectionNumber(vec![1]));
+    let mut setup = Chapter::new("Setup", SETUP.to_string(), "setup.md", vec!["Introduction".to_string()]);
+    setup.number = Some(SectionNumber(vec![1, 1]));
+    intro.sub_items.push(BookItem::Chapter(setup));
+
+    let mut book = Book::new();
+    book.push_item(BookItem::Chapter(intro));
+    book
+}
+
+fn context(root: &Path, translator: Value) -> PreprocessorContext {
+    serde_json::from_value(json!({
+        "root": root,
+        "config": {
+            "book": { "title": "Test", "src": "src" },
+            "preprocessor": { "translator": translator },
+        },
+        "renderer": "html",
+        "mdbook_version": mdbook::MDBOOK_VERSION,
+    }))
+    .unwrap()
+}
+
+fn run(ctx: &PreprocessorContext) -> mdbook::errors::Result<Book> {
+    let mut translator = DeepSeekTranslator::new();
+    translator.set_config(TranslatorConfig::from_table(ctx.config.get_preprocessor("translator"))?)?;
+    translator.run(ctx, book())
+}
+
+fn chapters(book: &Book) -> Vec<&Chapter> {
+    book.iter()
+        .filter_map(|item| match item {
+            BookItem::Chapter(chapter) => Some(chapter),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn translates_with_the_mock_backend_and_reuses_the_cache() {
+    let root = tempfile::tempdir().unwrap();
+    let ctx = context(root.path(), json!({ "language": "Chinese", "backend": "mock" }));
+
+    let translated = run(&ctx).unwrap();
+    let [intro, setup] = chapters(&translated)[..] else {
+        panic!("expected two chapters");
+    };
+
+    // 文字被伪本地化，代码块、链接地址、行内代码、自动链接和指令保持不变
+    assert_eq!(intro.name, "[Íñtródúçtíóñ ····]");
+    assert_eq!(setup.name, "[Sétúp ··]");
+    assert_eq!(setup.parent_names, ["[Íñtródúçtíóñ ····]"]);
+    assert_eq!(
+        intro.content,
+        "# [Géttíñg Stártéd ·····]
+
+[Hélló wórld, thís ís thé **fírst** çháptér. ·············]
+
+```rust
+fn main() {
+    println!(\"Hello, world!\");
+}
+```
+
+[Séé [thé sétúp gúídé](setup.md#install) áñd rúñ `cargo build`. ···············]
+
+{{#include ../listings/example.rs:main}}
+"
+    );
+    assert_eq!(
+        setup.content,
+        "## [Íñstáll ···]
+
+[Dówñlóád thé bíñárý fróm <https://example.com/download>. ···········]
+"
+    );
+
+    // 每个章节的文字和每个标题各一条缓存，受保护的内容以占位符的形式缓存
+    let cache_path = root.path().join(".translator-cache/zh.json");
+    let cache: Value = serde_json::from_str(&fs::read_to_string(&cache_path).unwrap()).unwrap();
+    let entries = cache["entries"].as_object().unwrap();
+    assert_eq!(entries.len(), 4, "{entries:?}");
+    let translations = entries
+        .values()
+        .map(|entry| entry["translation"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert!(translations.contains(&"[Íñtródúçtíóñ ····]"), "{translations:?}");
+    assert!(translations.contains(&"[Sétúp ··]"), "{translations:?}");
+    let intro_entry = translations.iter().find(|t| t.contains("Hélló")).unwrap();
+    assert!(intro_entry.contains("@@MDT0@@") && !intro_entry.contains("cargo build"), "{intro_entry}");
+    let fingerprint = &entries.values().next().unwrap()["fingerprint"];
+    assert!(fingerprint.is_string());
+    assert!(entries.values().all(|entry| &entry["fingerprint"] == fingerprint));
+    let cached = fs::read_to_string(&cache_path).unwrap();
+
+    // 第二次构建换成一个总是失败的后端：只有全部译文都来自缓存时才能成功
+    let ctx = context(
+        root.path(),
+        json!({ "language": "Chinese", "backend": "command", "backend-command": "false" }),
+    );
+    let retranslated = run(&ctx).unwrap();
+    assert_eq!(
+        serde_json::to_value(&retranslated).unwrap(),
+        serde_json::to_value(&translated).unwrap()
+    );
+    assert_eq!(fs::read_to_string(&cache_path).unwrap(), cached);
+}